@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use archive_rs::support::Format;
+use archive_rs::Archive;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+fn main() -> Result<()> {
+    let args = args();
+
+    if args.get_flag("list-archive-formats") {
+        for x in Format::all_file_endings() {
+            println!("{}", x);
+        }
+
+        return Ok(());
+    }
+
+    let src: &PathBuf = args.get_one("src").expect("`src` is required");
+    let dst: &PathBuf = args.get_one("dst").expect("`dst` is required");
+
+    let format = match args.get_one::<Format>("format") {
+        Some(format) => *format,
+        None => Format::from_path(dst).ok_or_else(|| {
+            anyhow!("unsupported archive file type: {}", dst.display())
+        })?,
+    };
+
+    let src = Archive::open(src)?;
+    let dst = BufWriter::new(File::create(dst)?);
+
+    archive_rs::convert(src, format, dst)?.flush()?;
+
+    Ok(())
+}
+
+fn args() -> ArgMatches {
+    cli().get_matches()
+}
+
+fn cli() -> Command {
+    let src = Arg::new("src")
+        .required_unless_present("list-archive-formats")
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("source archive file");
+
+    let dst = Arg::new("dst")
+        .required_unless_present("list-archive-formats")
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("destination archive file");
+
+    let format = Arg::new("format")
+        .short('f')
+        .long("format")
+        .value_parser(clap::value_parser!(Format))
+        .help("destination format, detected from the file name by default");
+
+    let help = Arg::new("help")
+        .short('?')
+        .long("help")
+        .action(ArgAction::Help)
+        .help("print help (use --help to see all options)")
+        .long_help("Print help.");
+
+    Command::new("archive-convert")
+        .arg(src)
+        .arg(dst)
+        .arg(format)
+        .arg(archive_rs::clap::list_archive_formats())
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .arg(help)
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use archive_rs::diff::{self, Field};
+use archive_rs::{Algorithm, Archive};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+fn main() -> Result<()> {
+    let args = args();
+
+    let old: &PathBuf = args.get_one("old").expect("`old` is required");
+    let new: &PathBuf = args.get_one("new").expect("`new` is required");
+
+    let name: &String = args
+        .get_one("algorithm")
+        .expect("`algorithm` has a default");
+
+    let algorithm = Algorithm::from_name(name)
+        .ok_or_else(|| anyhow!("unsupported algorithm: {name}"))?;
+
+    let mut old = Archive::open(old)?;
+    let mut new = Archive::open(new)?;
+
+    let diff = diff::compare(&mut old, &mut new, algorithm)?;
+
+    for path in &diff.added {
+        println!("A {}", path.display());
+    }
+
+    for path in &diff.removed {
+        println!("D {}", path.display());
+    }
+
+    for modified in &diff.modified {
+        let fields: Vec<&str> = modified
+            .fields
+            .iter()
+            .map(|field| match field {
+                Field::Type => "type",
+                Field::Content => "content",
+                Field::Mode => "mode",
+                Field::Owner => "owner",
+                Field::Mtime => "mtime",
+                Field::LinkTarget => "link target",
+                _ => "other",
+            })
+            .collect();
+
+        println!("M {} ({})", modified.path.display(), fields.join(", "));
+    }
+
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn args() -> ArgMatches {
+    cli().get_matches()
+}
+
+fn cli() -> Command {
+    let old = Arg::new("old")
+        .required(true)
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("old archive file");
+
+    let new = Arg::new("new")
+        .required(true)
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("new archive file");
+
+    let algorithm = Arg::new("algorithm")
+        .short('a')
+        .long("algorithm")
+        .default_value("sha256")
+        .help("digest algorithm to compare contents with");
+
+    let help = Arg::new("help")
+        .short('?')
+        .long("help")
+        .action(ArgAction::Help)
+        .help("print help (use --help to see all options)")
+        .long_help("Print help.");
+
+    Command::new("archive-diff")
+        .arg(old)
+        .arg(new)
+        .arg(algorithm)
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .arg(help)
+}
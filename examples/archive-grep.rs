@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use archive_rs::Archive;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::bytes::RegexBuilder;
+
+fn main() -> Result<()> {
+    let args = args();
+
+    let pattern: &String =
+        args.get_one("pattern").expect("`pattern` is required");
+
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(args.get_flag("ignore-case"))
+        .build()?;
+
+    let files_with_matches = args.get_flag("files-with-matches");
+    let count = args.get_flag("count");
+    let line_number = args.get_flag("line-number");
+
+    let archives: Vec<&PathBuf> = args
+        .get_many("archive")
+        .expect("`archive`s are required")
+        .collect();
+
+    let mut matched = false;
+
+    for path in archives {
+        let mut archive = Archive::open(path)?;
+        let mut search = archive.entries()?.search(&regex);
+        let mut counts: BTreeMap<PathBuf, u64> = BTreeMap::new();
+
+        while let Some(hit) = search.next() {
+            let hit = hit?;
+            matched = true;
+
+            if files_with_matches {
+                println!("{}:{}", path.display(), hit.path.display());
+                search.skip_entry();
+            } else if count {
+                *counts.entry(hit.path).or_default() += 1;
+            } else if hit.binary {
+                println!(
+                    "Binary file {}:{} matches",
+                    path.display(),
+                    hit.path.display()
+                );
+            } else if line_number {
+                println!(
+                    "{}:{}:{}:{}",
+                    path.display(),
+                    hit.path.display(),
+                    hit.line_number,
+                    hit.line
+                );
+            } else {
+                println!(
+                    "{}:{}:{}",
+                    path.display(),
+                    hit.path.display(),
+                    hit.line
+                );
+            }
+        }
+
+        for (entry, count) in counts {
+            println!("{}:{}:{}", path.display(), entry.display(), count);
+        }
+    }
+
+    if !matched {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn args() -> ArgMatches {
+    cli().get_matches()
+}
+
+fn cli() -> Command {
+    let pattern = Arg::new("pattern")
+        .required(true)
+        .help("regular expression");
+
+    let archive = Arg::new("archive")
+        .required(true)
+        .action(ArgAction::Append)
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("archive files");
+
+    let ignore_case = Arg::new("ignore-case")
+        .short('i')
+        .long("ignore-case")
+        .action(ArgAction::SetTrue)
+        .help("ignore case distinctions");
+
+    let files_with_matches = Arg::new("files-with-matches")
+        .short('l')
+        .long("files-with-matches")
+        .action(ArgAction::SetTrue)
+        .help("print only the names of matching entries");
+
+    let count = Arg::new("count")
+        .short('c')
+        .long("count")
+        .action(ArgAction::SetTrue)
+        .help("print only the number of matching lines per entry");
+
+    let line_number = Arg::new("line-number")
+        .short('n')
+        .long("line-number")
+        .action(ArgAction::SetTrue)
+        .help("print line numbers");
+
+    let help = Arg::new("help")
+        .short('?')
+        .long("help")
+        .action(ArgAction::Help)
+        .help("print help (use --help to see all options)")
+        .long_help("Print help.");
+
+    Command::new("archive-grep")
+        .arg(pattern)
+        .arg(archive)
+        .arg(ignore_case)
+        .arg(files_with_matches)
+        .arg(count)
+        .arg(line_number)
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .arg(help)
+}
@@ -1,7 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
-use archive_rs::{Archive, Entry};
+use archive_rs::{Archive, Entry, Wildcards};
 use bytesize::ByteSize;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 
@@ -29,10 +29,41 @@ fn main() -> Result<()> {
             .expect("`archive`s are required")
             .collect();
 
+        let ignore_case = args.get_flag("ignore-case");
+
+        let mut include = Wildcards::new();
+        include.case_insensitive(ignore_case);
+
+        let mut exclude = Wildcards::new();
+        exclude.anchored(false).case_insensitive(ignore_case);
+
+        for pattern in args.get_many::<String>("include").into_iter().flatten()
+        {
+            include.add(pattern);
+        }
+
+        for pattern in args.get_many::<String>("exclude").into_iter().flatten()
+        {
+            exclude.add(pattern);
+        }
+
+        let include = args
+            .contains_id("include")
+            .then(|| include.build())
+            .transpose()?;
+
+        let exclude = exclude.build()?;
+
         for path in archives {
             let mut archive = Archive::open(path)?;
 
-            for entry in archive.entries()? {
+            let mut entries = archive.entries()?.excluding(exclude.clone());
+
+            if let Some(include) = &include {
+                entries = entries.matching(include.clone());
+            }
+
+            for entry in entries {
                 let entry = entry?;
                 ls_entry(path, &entry, config)?;
             }
@@ -90,6 +121,23 @@ fn cli() -> Command {
         .action(ArgAction::SetTrue)
         .help("humanize bytes");
 
+    let include = Arg::new("include")
+        .long("include")
+        .action(ArgAction::Append)
+        .value_name("pattern")
+        .help("list only entries matching pattern");
+
+    let exclude = Arg::new("exclude")
+        .long("exclude")
+        .action(ArgAction::Append)
+        .value_name("pattern")
+        .help("don't list entries matching pattern");
+
+    let ignore_case = Arg::new("ignore-case")
+        .long("ignore-case")
+        .action(ArgAction::SetTrue)
+        .help("ignore case when matching patterns");
+
     let long = Arg::new("long")
         .short('l')
         .long("long")
@@ -107,6 +155,9 @@ fn cli() -> Command {
         .arg(archive)
         .arg(humanize)
         .arg(long)
+        .arg(include)
+        .arg(exclude)
+        .arg(ignore_case)
         .arg(archive_rs::clap::list_archive_formats())
         .disable_help_flag(true)
         .disable_version_flag(true)
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use archive_rs::Archive;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+fn main() -> Result<()> {
+    let args = args();
+
+    let all = args.get_flag("all");
+
+    let archives: Vec<&PathBuf> = args
+        .get_many("archive")
+        .expect("`archive`s are required")
+        .collect();
+
+    let mut ok = true;
+
+    for path in archives {
+        let archive = Archive::open(path)?;
+
+        let report = if all {
+            archive.test_all()?
+        } else {
+            archive.test()?
+        };
+
+        if report.is_ok() {
+            println!(
+                "{}: OK, {} entries, {} bytes",
+                path.display(),
+                report.entries,
+                report.bytes
+            );
+        }
+
+        for failure in &report.failures {
+            ok = false;
+
+            let entry = match (&failure.path, failure.offset) {
+                (Some(entry), _) => format!(" {}:", entry.display()),
+                (None, Some(offset)) => format!(" offset {offset}:"),
+                (None, None) => String::new(),
+            };
+
+            println!("{}:{} {}", path.display(), entry, failure.error);
+        }
+    }
+
+    if !ok {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn args() -> ArgMatches {
+    cli().get_matches()
+}
+
+fn cli() -> Command {
+    let archive = Arg::new("archive")
+        .required(true)
+        .action(ArgAction::Append)
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("archive files");
+
+    let all = Arg::new("all")
+        .short('a')
+        .long("all")
+        .action(ArgAction::SetTrue)
+        .help("continue after the first failure");
+
+    let help = Arg::new("help")
+        .short('?')
+        .long("help")
+        .action(ArgAction::Help)
+        .help("print help (use --help to see all options)")
+        .long_help("Print help.");
+
+    Command::new("archive-test")
+        .arg(archive)
+        .arg(all)
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .arg(help)
+}
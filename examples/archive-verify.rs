@@ -0,0 +1,128 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use archive_rs::{Algorithm, Archive, Manifest, ManifestFormat};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+fn main() -> Result<()> {
+    let args = args();
+
+    let archive: &PathBuf =
+        args.get_one("archive").expect("`archive` is required");
+
+    let manifest: &PathBuf =
+        args.get_one("manifest").expect("`manifest` is required");
+
+    let format = if args.get_flag("tag") {
+        ManifestFormat::Bsd
+    } else if args.get_flag("json") {
+        ManifestFormat::Json
+    } else {
+        let name: &String = args
+            .get_one("algorithm")
+            .expect("`algorithm` has a default");
+
+        let algorithm = Algorithm::from_name(name)
+            .ok_or_else(|| anyhow!("unsupported algorithm: {name}"))?;
+
+        ManifestFormat::Sum(algorithm)
+    };
+
+    let quiet = args.get_flag("quiet");
+
+    let manifest = File::open(manifest)?;
+    let manifest = Manifest::read(BufReader::new(manifest), format)?;
+
+    let mut archive = Archive::open(archive)?;
+    let verification = archive.verify(&manifest)?;
+
+    if !quiet {
+        for path in &verification.matched {
+            println!("{}: OK", path.display());
+        }
+    }
+
+    for mismatch in &verification.mismatched {
+        println!(
+            "{}: FAILED {} expected {} actual {}",
+            mismatch.path.display(),
+            mismatch.algorithm,
+            mismatch.expected,
+            mismatch.actual,
+        );
+    }
+
+    for path in &verification.missing {
+        println!("{}: MISSING", path.display());
+    }
+
+    for path in &verification.extra {
+        println!("{}: EXTRA", path.display());
+    }
+
+    if !verification.is_ok() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn args() -> ArgMatches {
+    cli().get_matches()
+}
+
+fn cli() -> Command {
+    let archive = Arg::new("archive")
+        .required(true)
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("archive file");
+
+    let manifest = Arg::new("manifest")
+        .required(true)
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("checksum manifest");
+
+    let algorithm = Arg::new("algorithm")
+        .short('a')
+        .long("algorithm")
+        .default_value("sha256")
+        .help("digest algorithm of a sha256sum-style manifest");
+
+    let tag = Arg::new("tag")
+        .long("tag")
+        .action(ArgAction::SetTrue)
+        .conflicts_with_all(["algorithm", "json"])
+        .help("read a BSD-style manifest");
+
+    let json = Arg::new("json")
+        .long("json")
+        .action(ArgAction::SetTrue)
+        .conflicts_with("algorithm")
+        .help("read a JSON manifest");
+
+    let quiet = Arg::new("quiet")
+        .short('q')
+        .long("quiet")
+        .action(ArgAction::SetTrue)
+        .help("don't print OK for each verified file");
+
+    let help = Arg::new("help")
+        .short('?')
+        .long("help")
+        .action(ArgAction::Help)
+        .help("print help (use --help to see all options)")
+        .long_help("Print help.");
+
+    Command::new("archive-verify")
+        .arg(archive)
+        .arg(manifest)
+        .arg(algorithm)
+        .arg(tag)
+        .arg(json)
+        .arg(quiet)
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .arg(help)
+}
@@ -1,17 +1,15 @@
-#[cfg(feature = "tar")]
-use std::fs::File;
+use std::cmp::Ordering;
 #[cfg(feature = "zstd")]
 use std::io::BufReader;
-use std::path::Path;
-
-use path_utils::PathExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[cfg(feature = "bzip2")]
 use bzip2::read::BzDecoder as BzSysDecoder;
 #[cfg(feature = "bzip2-rs")]
 use bzip2_rs::decoder::DecoderReader as BzNativeDecoder;
 #[cfg(feature = "flate2")]
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 #[cfg(feature = "lz4")]
 use lz4::Decoder as Lz4Decoder;
 #[cfg(feature = "tar")]
@@ -21,108 +19,452 @@ use xz2::read::XzDecoder;
 #[cfg(feature = "zstd")]
 use zstd::stream::read::Decoder as ZstdDecoder;
 
-use crate::Result;
+#[cfg(all(
+    feature = "tar",
+    any(feature = "flate2", feature = "xz2", feature = "zstd")
+))]
+use crate::parallel::{Codec, Parallel};
+#[cfg(feature = "tar")]
+use crate::read_ahead::ReadAhead;
+#[cfg(all(feature = "tar", any(feature = "xz2", feature = "zstd")))]
+use crate::seekable::{Seekable, SeekableTar};
+use crate::source::{Source, State, Tracked};
+use crate::support::Format;
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+use crate::XattrPolicy;
+use crate::{Entry, OpenOptions, Progress, Result};
 
 /// Archive file.
+pub struct Archive {
+    pub(crate) inner: Inner,
+    pub(crate) format: Format,
+    pub(crate) state: Arc<State>,
+    pub(crate) observer: Option<Box<Observer>>,
+    pub(crate) paths: Vec<PathBuf>,
+    pub(crate) options: OpenOptions,
+    #[cfg(all(feature = "xattr", target_os = "linux"))]
+    pub(crate) unpack_xattrs: Option<XattrPolicy>,
+}
+
+/// Progress observer, see [`Archive::set_progress_observer`].
+pub type Observer = dyn FnMut(&Progress<'_>) + Send;
+
+/// Archive reader of a specific format.
 // NONEXHAUSTIVE new formats could add new types
 #[non_exhaustive]
 // ALLOW constructing and storing Self is not the bottleneck, iterating the
 // entries and I/O is
 #[allow(clippy::large_enum_variant)]
-pub enum Archive {
+pub enum Inner {
     #[cfg(feature = "tar")]
-    #[doc(hidden)]
-    Tar(Tar<File>),
+    Tar(Tar<Tracked<Source>>),
 
     #[cfg(all(feature = "bzip2", feature = "tar"))]
-    #[doc(hidden)]
-    TarBzip2(Tar<BzSysDecoder<File>>),
+    TarBzip2(Tar<Tracked<BzSysDecoder<Source>>>),
 
     #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-    #[doc(hidden)]
-    TarBzip2Rs(Tar<BzNativeDecoder<File>>),
+    TarBzip2Rs(Tar<Tracked<BzNativeDecoder<Source>>>),
 
     #[cfg(all(feature = "flate2", feature = "tar"))]
-    #[doc(hidden)]
-    TarGzip(Tar<GzDecoder<File>>),
+    TarGzip(Tar<Tracked<MultiGzDecoder<Source>>>),
 
     #[cfg(all(feature = "lz4", feature = "tar"))]
-    #[doc(hidden)]
-    TarLz4(Tar<Lz4Decoder<File>>),
+    TarLz4(Tar<Tracked<Lz4Decoder<Source>>>),
 
     #[cfg(all(feature = "xz2", feature = "tar"))]
-    #[doc(hidden)]
-    TarXz(Tar<XzDecoder<File>>),
+    TarXz(Tar<Tracked<XzDecoder<Source>>>),
+
+    #[cfg(all(feature = "xz2", feature = "tar"))]
+    TarXzSeekable(SeekableTar),
+
+    #[cfg(all(feature = "zstd", feature = "tar"))]
+    TarZstd(Tar<Tracked<ZstdDecoder<'static, BufReader<Source>>>>),
 
     #[cfg(all(feature = "zstd", feature = "tar"))]
-    #[doc(hidden)]
-    TarZstd(Tar<ZstdDecoder<'static, BufReader<File>>>),
+    TarZstdSeekable(SeekableTar),
+
+    #[cfg(all(
+        feature = "tar",
+        any(feature = "flate2", feature = "xz2", feature = "zstd")
+    ))]
+    TarParallel(Tar<Tracked<Parallel>>),
+
+    #[cfg(feature = "tar")]
+    TarReadAhead(Tar<Tracked<ReadAhead>>),
 }
 
 impl Archive {
     /// Returns an opened archive file.
     ///
+    /// If `path` is the first volume of an archive split into numbered
+    /// volumes, e.g. `backup.tar.zst.000`, the following volumes are read
+    /// after it, see [`Volumes`](crate::Volumes).
+    ///
     /// # Errors
     ///
     /// Returns an error if archive is of unsupported format or if opening it
     /// fails.
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
-        let path = path.as_ref();
+        OpenOptions::new().open(path)
+    }
+
+    /// Returns an opened archive file that consists of the volumes at
+    /// `paths`, which are read one after another.
+    ///
+    /// The volumes of a GNU tar multi-volume tarball, as written by `tar
+    /// --multi-volume`, read as one tarball, including entries that span
+    /// volumes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if archive is of unsupported format, as detected from
+    /// the first path, or if opening it fails.
+    pub fn open_volumes(
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> Result<Self> {
+        OpenOptions::new().open_volumes(paths)
+    }
+
+    pub(crate) fn open_with(
+        paths: &[PathBuf],
+        options: &OpenOptions,
+    ) -> Result<Self> {
+        let path = paths.first().map_or_else(PathBuf::new, Clone::clone);
+
+        let format = Format::from_path(&path)
+            .ok_or(crate::Error::UnsupportedArchiveType(path))?;
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "open",
+            path = %paths[0].display(),
+            volumes = paths.len(),
+            format = format.name(),
+        )
+        .entered();
 
-        let extensions: Vec<String> = path.extensions_lossy().collect();
-        let extensions: Vec<&str> =
-            extensions.iter().map(String::as_str).collect();
+        let state = State::new(options.cancellation.clone());
+        let inner = Self::open_inner(format, paths, &state, options)?;
 
-        match extensions.as_slice() {
+        Ok(Self {
+            inner,
+            format,
+            state,
+            observer: None,
+            paths: paths.to_vec(),
+            options: options.clone(),
+            #[cfg(all(feature = "xattr", target_os = "linux"))]
+            unpack_xattrs: None,
+        })
+    }
+
+    /// Returns this archive opened again, positioned at its start.
+    pub(crate) fn reopen(&self) -> Result<Self> {
+        Self::open_with(&self.paths, &self.options)
+    }
+
+    // ALLOW one match arm per supported format
+    #[allow(clippy::too_many_lines)]
+    fn open_inner(
+        format: Format,
+        paths: &[PathBuf],
+        state: &Arc<State>,
+        options: &OpenOptions,
+    ) -> Result<Inner> {
+        match format {
             #[cfg(all(feature = "bzip2", feature = "tar"))]
-            ["tbz" | "tbz2", ..] | ["bz2", "tar", ..] => {
-                let file = File::open(path)?;
+            Format::TarBzip2 => {
+                let file = Source::open(paths, state)?;
                 let file = BzSysDecoder::new(file);
-                Ok(Self::TarBzip2(Tar::new(file)))
+
+                if options.read_ahead > 0 {
+                    let file = ReadAhead::new(file, options.read_ahead)?;
+                    return Ok(Inner::TarReadAhead(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                Ok(Inner::TarBzip2(Tar::new(Tracked::new(file, state))))
             }
 
             #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-            ["tbz" | "tbz2", ..] | ["bz2", "tar", ..] => {
-                let file = File::open(path)?;
+            Format::TarBzip2 => {
+                let file = Source::open(paths, state)?;
                 let file = BzNativeDecoder::new(file);
-                Ok(Self::TarBzip2Rs(Tar::new(file)))
+
+                if options.read_ahead > 0 {
+                    let file = ReadAhead::new(file, options.read_ahead)?;
+                    return Ok(Inner::TarReadAhead(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                Ok(Inner::TarBzip2Rs(Tar::new(Tracked::new(file, state))))
             }
 
             #[cfg(all(feature = "flate2", feature = "tar"))]
-            ["tgz", ..] | ["gz", "tar", ..] => {
-                let file = File::open(path)?;
-                let file = GzDecoder::new(file);
-                Ok(Self::TarGzip(Tar::new(file)))
+            Format::TarGzip => {
+                let file = Source::open(paths, state)?;
+
+                if options.threads > 1 {
+                    let file = Self::parallel(file, Codec::Gzip, options)?;
+                    return Ok(Inner::TarParallel(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                let file = MultiGzDecoder::new(file);
+
+                if options.read_ahead > 0 {
+                    let file = ReadAhead::new(file, options.read_ahead)?;
+                    return Ok(Inner::TarReadAhead(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                Ok(Inner::TarGzip(Tar::new(Tracked::new(file, state))))
             }
 
             #[cfg(all(feature = "lz4", feature = "tar"))]
-            ["lz4", "tar", ..] => {
-                let file = File::open(path)?;
+            Format::TarLz4 => {
+                let file = Source::open(paths, state)?;
                 let file = Lz4Decoder::new(file)?;
-                Ok(Self::TarLz4(Tar::new(file)))
+
+                if options.read_ahead > 0 {
+                    let file = ReadAhead::new(file, options.read_ahead)?;
+                    return Ok(Inner::TarReadAhead(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                Ok(Inner::TarLz4(Tar::new(Tracked::new(file, state))))
             }
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
-            ["txz", ..] | ["xz", "tar", ..] => {
-                let file = File::open(path)?;
-                let file = XzDecoder::new(file);
-                Ok(Self::TarXz(Tar::new(file)))
+            Format::TarXz => {
+                let file = Source::open(paths, state)?;
+
+                if options.threads > 1 {
+                    let file = Self::parallel(file, Codec::Xz, options)?;
+                    return Ok(Inner::TarParallel(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                if options.read_ahead > 0 {
+                    let file = XzDecoder::new(file);
+                    let file = ReadAhead::new(file, options.read_ahead)?;
+                    return Ok(Inner::TarReadAhead(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                match Seekable::xz(file)? {
+                    Ok(file) => {
+                        Ok(Inner::TarXzSeekable(SeekableTar::new(file, state)))
+                    }
+                    Err(file) => {
+                        let file = XzDecoder::new(file);
+                        Ok(Inner::TarXz(Tar::new(Tracked::new(file, state))))
+                    }
+                }
             }
 
             #[cfg(all(feature = "zstd", feature = "tar"))]
-            ["zst", "tar", ..] => {
-                let file = File::open(path)?;
-                let file = ZstdDecoder::new(file)?;
-                Ok(Self::TarZstd(Tar::new(file)))
+            Format::TarZstd => {
+                let file = Source::open(paths, state)?;
+
+                if options.threads > 1 {
+                    let file = Self::parallel(file, Codec::Zstd, options)?;
+                    return Ok(Inner::TarParallel(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                if options.read_ahead > 0 {
+                    let file = ZstdDecoder::new(file)?;
+                    let file = ReadAhead::new(file, options.read_ahead)?;
+                    return Ok(Inner::TarReadAhead(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                match Seekable::zstd(file)? {
+                    Ok(file) => Ok(Inner::TarZstdSeekable(SeekableTar::new(
+                        file, state,
+                    ))),
+                    Err(file) => {
+                        let file = ZstdDecoder::new(file)?;
+                        Ok(Inner::TarZstd(Tar::new(Tracked::new(file, state))))
+                    }
+                }
             }
 
             #[cfg(feature = "tar")]
-            ["tar", ..] => {
-                let file = File::open(path)?;
-                Ok(Self::Tar(Tar::new(file)))
+            Format::Tar => {
+                let file = Source::open(paths, state)?.gnu_volumes()?;
+
+                if options.read_ahead > 0 {
+                    let file = ReadAhead::new(file, options.read_ahead)?;
+                    return Ok(Inner::TarReadAhead(Tar::new(Tracked::new(
+                        file, state,
+                    ))));
+                }
+
+                Ok(Inner::Tar(Tar::new(Tracked::new(file, state))))
+            }
+        }
+    }
+
+    #[cfg(all(
+        feature = "tar",
+        any(feature = "flate2", feature = "xz2", feature = "zstd")
+    ))]
+    fn parallel(
+        file: Source,
+        codec: Codec,
+        options: &OpenOptions,
+    ) -> Result<Parallel> {
+        let file =
+            Parallel::new(file, codec, options.threads, options.memory_limit)?;
+        Ok(file)
+    }
+
+    /// Returns the format of this archive, as detected from its file name.
+    #[must_use]
+    pub const fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the entry whose headers start at `offset` of the uncompressed
+    /// archive, as returned by [`Entry::offset`], or `None` if there are no
+    /// entries at or after `offset`.
+    ///
+    /// Zstd-compressed tarballs in the [seekable format] and xz-compressed
+    /// tarballs with multiple blocks, as written by `xz -T`, are repositioned
+    /// directly and only the blocks containing the entry are decompressed.
+    /// All other archives fall back to streaming through the entries up to
+    /// `offset`, which is only possible as long as the entries of this archive
+    /// have not been iterated yet.
+    ///
+    /// Afterwards, seekable archives can be repositioned again with this
+    /// method, but [`Archive::entries`] fails for all archives. Open the
+    /// archive again to iterate its entries from the start.
+    ///
+    /// [seekable format]: https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the archive fails, if the archive has
+    /// already been iterated and cannot be repositioned or if there is no
+    /// entry header at `offset`.
+    pub fn read_entry_at(&mut self, offset: u64) -> Result<Option<Entry<'_>>> {
+        let seekable = match &mut self.inner {
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Inner::TarXzSeekable(archive) => {
+                archive.seek_to(offset);
+                true
+            }
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Inner::TarZstdSeekable(archive) => {
+                archive.seek_to(offset);
+                true
+            }
+
+            #[allow(unreachable_patterns)]
+            _ => false,
+        };
+
+        let mut entries = self.entries()?;
+
+        if seekable {
+            return entries.next().transpose();
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(offset, "archive is not seekable, streaming to entry");
+
+        for entry in entries {
+            let entry = entry?;
+
+            match entry.offset().cmp(&offset) {
+                Ordering::Less => {}
+                Ordering::Equal => return Ok(Some(entry)),
+                Ordering::Greater => {
+                    return Err(crate::Error::InvalidOffset(offset));
+                }
             }
+        }
+
+        Ok(None)
+    }
+
+    /// Extracts all entries of this archive into the directory `dst`.
+    ///
+    /// Entries whose paths would be outside of `dst`, e.g. because they
+    /// contain `..`, are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the archive or creating any of the files
+    /// fails.
+    pub fn unpack(&mut self, dst: impl AsRef<Path>) -> Result<()> {
+        let dst = dst.as_ref();
+
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("unpack", dst = %dst.display()).entered();
+
+        std::fs::create_dir_all(dst)?;
+
+        #[cfg(all(feature = "xattr", target_os = "linux"))]
+        let xattrs = self.unpack_xattrs;
 
-            _ => Err(crate::Error::UnsupportedArchiveType(path.to_path_buf())),
+        for entry in self.entries()? {
+            let mut entry = entry?;
+
+            #[cfg(all(feature = "xattr", target_os = "linux"))]
+            if let Some(policy) = xattrs {
+                entry.unpack_in_with_xattrs(dst, policy)?;
+                continue;
+            }
+
+            entry.unpack_in(dst)?;
         }
+
+        Ok(())
+    }
+
+    /// Sets the observer that is called with the [`Progress`] after every
+    /// entry returned by [`Archive::entries`].
+    ///
+    /// Since the observer is called between entries, reading the data of a
+    /// large entry is only reported with the next entry.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use archive_rs::Archive;
+    ///
+    /// let mut archive = Archive::open("backup.tar.zst")?;
+    ///
+    /// archive.set_progress_observer(|progress| {
+    ///     eprintln!(
+    ///         "{}/{} bytes, {} entries",
+    ///         progress.compressed, progress.compressed_total, progress.entries,
+    ///     );
+    /// });
+    ///
+    /// for entry in archive.entries()? {
+    ///     entry?;
+    /// }
+    /// # Ok::<(), archive_rs::Error>(())
+    /// ```
+    pub fn set_progress_observer(
+        &mut self,
+        observer: impl FnMut(&Progress<'_>) + Send + 'static,
+    ) {
+        self.observer = Some(Box::new(observer));
     }
 }
@@ -0,0 +1,322 @@
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+use std::fs;
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+use std::io;
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+use std::path::Path;
+
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+use crate::entry::unpack_path;
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+use crate::{Archive, EntryType};
+use crate::{Entry, Error, Result};
+
+/// Prefix of the PAX records of extended attributes.
+const XATTR: &str = "SCHILY.xattr.";
+
+/// PAX record of the POSIX.1e access ACL.
+const ACL_ACCESS: &str = "SCHILY.acl.access";
+
+/// PAX record of the POSIX.1e default ACL of directories.
+const ACL_DEFAULT: &str = "SCHILY.acl.default";
+
+/// PAX record of the security context, as written by `tar --selinux`.
+const SELINUX: &str = "RHT.security.selinux";
+
+/// Extended attribute that holds the security context.
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+const SELINUX_XATTR: &str = "security.selinux";
+
+/// Extended attributes, ACLs and security context of an entry, see
+/// [`Entry::attributes`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Attributes {
+    /// Extended attributes, from `SCHILY.xattr.*` records.
+    pub xattrs: Vec<Xattr>,
+
+    /// Access ACL, from the `SCHILY.acl.access` record.
+    pub access_acl: Vec<AclEntry>,
+
+    /// Default ACL of directories, from the `SCHILY.acl.default` record.
+    pub default_acl: Vec<AclEntry>,
+
+    /// Security context, e.g. `system_u:object_r:etc_t:s0`, from the
+    /// `RHT.security.selinux` record.
+    pub selinux_context: Option<String>,
+}
+
+/// Extended attribute.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Xattr {
+    /// Name including the namespace, e.g. `user.comment`.
+    pub name: String,
+
+    /// Raw value.
+    pub value: Vec<u8>,
+}
+
+/// Entry of a POSIX.1e ACL, e.g. `user:alice:rw-`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AclEntry {
+    /// Whom the entry applies to.
+    pub tag: AclTag,
+
+    /// Name of the user or group of [`AclTag::User`] and [`AclTag::Group`]
+    /// entries.
+    pub qualifier: Option<String>,
+
+    /// Numeric ID of the user or group, if stored or if the qualifier is
+    /// numeric.
+    pub id: Option<u32>,
+
+    /// Permissions as bits of `0o7`, e.g. `0o6` for `rw-`.
+    pub permissions: u32,
+}
+
+/// Tag of an [`AclEntry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// NONEXHAUSTIVE NFSv4 ACLs could add new tags
+#[non_exhaustive]
+pub enum AclTag {
+    /// Owner of the file, `user::`.
+    UserObj,
+
+    /// Named user, `user:name:`.
+    User,
+
+    /// Owning group of the file, `group::`.
+    GroupObj,
+
+    /// Named group, `group:name:`.
+    Group,
+
+    /// Maximum permissions of named users and groups and the owning group,
+    /// `mask::`.
+    Mask,
+
+    /// Everyone else, `other::`.
+    Other,
+}
+
+/// Which extended attributes are restored when extracting, see
+/// [`Archive::set_unpack_xattrs`].
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+// NONEXHAUSTIVE new policies could restore other sets of namespaces
+#[non_exhaustive]
+pub enum XattrPolicy {
+    /// Restores only the `user.*` namespace, which unprivileged processes
+    /// can write.
+    #[default]
+    UserOnly,
+
+    /// Restores all namespaces, including `security.*`, e.g. the security
+    /// context in `security.selinux`, and `trusted.*`, which need
+    /// privileges.
+    All,
+}
+
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+impl XattrPolicy {
+    /// Returns true if the extended attribute `name` is restored.
+    fn restores(self, name: &str) -> bool {
+        self == Self::All || name.starts_with("user.")
+    }
+}
+
+impl Entry<'_> {
+    /// Returns the extended attributes, ACLs and security context of this
+    /// entry, decoded from the PAX records written by e.g. `tar --xattrs
+    /// --acls --selinux` and `star`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the PAX records fails or if an ACL is
+    /// malformed.
+    pub fn attributes(&mut self) -> Result<Attributes> {
+        let mut attributes = Attributes::default();
+
+        for (key, value) in self.pax_extensions()? {
+            if let Some(name) = key.strip_prefix(XATTR) {
+                attributes.xattrs.push(Xattr {
+                    name: name.to_owned(),
+                    value,
+                });
+
+                continue;
+            }
+
+            match key.as_str() {
+                ACL_ACCESS => attributes.access_acl = acl(&value)?,
+                ACL_DEFAULT => attributes.default_acl = acl(&value)?,
+                SELINUX => {
+                    let context = String::from_utf8_lossy(&value);
+                    let context = context.trim_end_matches('\0');
+                    attributes.selinux_context = Some(context.to_owned());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Extracts this entry into the directory `dst` like
+    /// [`Entry::unpack_in`], and restores the extended attributes that
+    /// `policy` allows, including the security context as
+    /// `security.selinux`.
+    ///
+    /// Extended attributes of symbolic links are not restored, and ACLs are
+    /// not restored unless they are stored as `system.posix_acl_*` extended
+    /// attributes, so malformed ACLs do not fail extraction. Files that are
+    /// not writable by their owner are made writable while restoring.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if creating the file, reading the entry or setting
+    /// an extended attribute fails, e.g. because the file system does not
+    /// support them.
+    #[cfg(all(feature = "xattr", target_os = "linux"))]
+    pub fn unpack_in_with_xattrs(
+        &mut self,
+        dst: impl AsRef<Path>,
+        policy: XattrPolicy,
+    ) -> Result<bool> {
+        let dst = dst.as_ref();
+
+        let xattrs = self
+            .pax_extensions()?
+            .into_iter()
+            .filter_map(|(key, value)| match key.strip_prefix(XATTR) {
+                Some(name) => Some((name.to_owned(), value)),
+                None if key == SELINUX => {
+                    let context = String::from_utf8_lossy(&value);
+                    let context = context.trim_end_matches('\0').as_bytes();
+                    Some((SELINUX_XATTR.to_owned(), context.to_vec()))
+                }
+                None => None,
+            })
+            .filter(|(name, _)| policy.restores(name))
+            .collect::<Vec<_>>();
+
+        if !self.unpack_in(dst)? {
+            return Ok(false);
+        }
+
+        if xattrs.is_empty() || self.entry_type() == EntryType::Symlink {
+            return Ok(true);
+        }
+
+        let Some(path) = unpack_path(dst, &self.path()?) else {
+            return Ok(true);
+        };
+
+        // setting extended attributes needs write permission, which the
+        // restored mode may lack
+        let permissions = fs::metadata(&path)?.permissions();
+        let read_only = permissions.mode() & 0o200 == 0;
+
+        if read_only {
+            let writable = permissions.mode() | 0o200;
+            fs::set_permissions(&path, fs::Permissions::from_mode(writable))?;
+        }
+
+        let restored = xattrs.iter().try_for_each(|(name, value)| {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(path = %path.display(), name, "restoring xattr");
+
+            xattr::set(&path, name, value).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("setting xattr {name} of {}: {e}", path.display()),
+                )
+            })
+        });
+
+        if read_only {
+            fs::set_permissions(&path, permissions)?;
+        }
+
+        restored?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+impl Archive {
+    /// Sets which extended attributes [`Archive::unpack`] restores, see
+    /// [`Entry::unpack_in_with_xattrs`], none by default.
+    pub const fn set_unpack_xattrs(&mut self, policy: Option<XattrPolicy>) {
+        self.unpack_xattrs = policy;
+    }
+}
+
+/// Returns the entries of the ACL in the text form `value`, separated by
+/// commas or newlines, with an optional numeric ID after the permissions as
+/// written by `star`.
+fn acl(value: &[u8]) -> Result<Vec<AclEntry>> {
+    let text = String::from_utf8_lossy(value);
+
+    text.split([',', '\n'])
+        .map(|entry| entry.split('#').next().unwrap_or_default().trim())
+        .filter(|entry| !entry.is_empty())
+        .map(acl_entry)
+        .collect()
+}
+
+/// Returns the ACL entry in the text form `entry`, e.g. `user:alice:rw-`.
+fn acl_entry(entry: &str) -> Result<AclEntry> {
+    let invalid = || Error::InvalidAcl(entry.to_owned());
+
+    let mut fields = entry.split(':');
+    let tag = fields.next().ok_or_else(invalid)?;
+    let qualifier = fields.next().ok_or_else(invalid)?;
+    let permissions = fields.next().ok_or_else(invalid)?;
+    let id = fields.next();
+
+    if fields.next().is_some() {
+        return Err(invalid());
+    }
+
+    let named = !qualifier.is_empty();
+
+    let tag = match tag {
+        "user" | "u" if named => AclTag::User,
+        "user" | "u" => AclTag::UserObj,
+        "group" | "g" if named => AclTag::Group,
+        "group" | "g" => AclTag::GroupObj,
+        "mask" | "m" if !named => AclTag::Mask,
+        "other" | "o" if !named => AclTag::Other,
+        _ => return Err(invalid()),
+    };
+
+    let id = match id {
+        Some(id) => Some(id.parse().map_err(|_| invalid())?),
+        None => qualifier.parse().ok(),
+    };
+
+    let mut bits = 0;
+
+    for (i, permission) in permissions.bytes().enumerate() {
+        bits |= match (i, permission) {
+            (0, b'r') => 0o4,
+            (1, b'w') => 0o2,
+            (2, b'x') => 0o1,
+            (0..=2, b'-') => 0,
+            _ => return Err(invalid()),
+        };
+    }
+
+    Ok(AclEntry {
+        tag,
+        qualifier: named.then(|| qualifier.to_owned()),
+        id,
+        permissions: bits,
+    })
+}
@@ -0,0 +1,629 @@
+use std::borrow::Cow;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+#[cfg(feature = "bzip2")]
+use bzip2::write::BzEncoder;
+#[cfg(feature = "flate2")]
+use flate2::write::GzEncoder;
+#[cfg(feature = "flate2")]
+use flate2::GzBuilder;
+#[cfg(feature = "lz4")]
+use lz4::Encoder as Lz4Encoder;
+#[cfg(feature = "xz2")]
+use xz2::write::XzEncoder;
+#[cfg(feature = "zstd")]
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+use crate::source::{Source, State, Tracked};
+use crate::support::Format;
+use crate::volume::is_volume_number;
+use crate::{Archive, Entry, Error, Result};
+
+/// Writer of a new archive.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+///
+/// use archive_rs::support::Format;
+/// use archive_rs::{Archive, Builder};
+///
+/// let mut src = Archive::open("backup.tar.gz")?;
+/// let dst = File::create("backup.tar.zst")?;
+/// let mut builder = Builder::new(dst, Format::TarZstd)?;
+///
+/// for entry in src.entries()? {
+///     builder.append_entry(&mut entry?)?;
+/// }
+///
+/// builder.finish()?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+pub struct Builder<W: Write> {
+    inner: tar::Builder<Encoder<W>>,
+    source_date_epoch: Option<u64>,
+}
+
+impl Archive {
+    /// Opens the uncompressed tarball at `path` to append entries to it in
+    /// place.
+    ///
+    /// The end-of-archive blocks and anything after them are truncated, so
+    /// the returned builder continues after the last entry and writes new
+    /// end-of-archive blocks when it is finished.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use archive_rs::Archive;
+    ///
+    /// let mut builder = Archive::open_append("backup.tar")?;
+    /// builder.append_dir_all("spool", "/var/spool/agent")?;
+    /// builder.finish()?;
+    /// # Ok::<(), archive_rs::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` is not a plain tarball, since the
+    /// end-of-archive blocks of a compressed tarball cannot be removed
+    /// without recompressing it, if it is a volume of a split archive, see
+    /// [`Volumes`](crate::Volumes), or if reading the entries or truncating
+    /// fails.
+    pub fn open_append(path: impl AsRef<Path>) -> Result<Builder<File>> {
+        let path = path.as_ref();
+
+        // truncating a volume would cut the set short
+        if path
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(is_volume_number)
+        {
+            return Err(Error::UnsupportedAppendVolume(path.to_owned()));
+        }
+
+        let format = Format::from_path(path)
+            .ok_or_else(|| Error::UnsupportedArchiveType(path.to_owned()))?;
+
+        if format != Format::Tar {
+            return Err(Error::UnsupportedAppendFormat(format));
+        }
+
+        let state = State::new(None);
+        let source = Source::open(&[path.to_owned()], &state)?;
+        let mut archive = tar::Archive::new(Tracked::new(source, &state));
+
+        let mut end = 0;
+
+        // seeks over the data instead of reading it, and the data of an entry
+        // follows its headers, which tar has just read
+        for entry in archive.entries_with_seek()? {
+            let size = Entry::from_tar(entry, 0, Entry::Tar)?.stored_size()?;
+            end = state.tarball_position() + size.next_multiple_of(BLOCK);
+        }
+
+        let mut file = OpenOptions::new().write(true).open(path)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(path = %path.display(), end, "appending to tarball");
+
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+
+        Builder::new(file, format)
+    }
+}
+
+/// Size of tar blocks.
+const BLOCK: u64 = 512;
+
+/// Compressing writer of a specific format.
+// ALLOW constructing and storing Self is not the bottleneck, compression is
+#[allow(clippy::large_enum_variant)]
+enum Encoder<W: Write> {
+    Tar(W),
+
+    #[cfg(feature = "bzip2")]
+    Bzip2(BzEncoder<W>),
+
+    #[cfg(feature = "flate2")]
+    Gzip(GzEncoder<W>),
+
+    #[cfg(feature = "lz4")]
+    Lz4(Lz4Encoder<W>),
+
+    #[cfg(feature = "xz2")]
+    Xz(XzEncoder<W>),
+
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdEncoder<'static, W>),
+}
+
+impl<W: Write> Builder<W> {
+    /// Returns a builder that writes an archive of `format` to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `format` cannot be written, e.g. bzip2 with the
+    /// `bzip2-rs` feature, which only decompresses, or if initializing the
+    /// compressor fails.
+    pub fn new(writer: W, format: Format) -> Result<Self> {
+        Self::create(writer, format, None)
+    }
+
+    /// Returns a builder that writes a bit-identical archive of `format` to
+    /// `writer` for the same input, like `tar --sort=name --mtime
+    /// --clamp-mtime --owner=0 --group=0 --numeric-owner`.
+    ///
+    /// Modification times later than `source_date_epoch` are clamped to it,
+    /// owners are zeroed and permissions are normalized to `0755` for
+    /// directories and executables and `0644` for everything else. Access
+    /// and change times are not written. Directories added with
+    /// [`append_dir_all`](Self::append_dir_all) are always sorted by path,
+    /// other entries are written in the order they are appended.
+    ///
+    /// Compressors are single-threaded and gzip headers have neither a
+    /// timestamp nor an operating system, so the compressed output is
+    /// reproducible too.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    ///
+    /// use archive_rs::support::Format;
+    /// use archive_rs::Builder;
+    ///
+    /// let epoch = archive_rs::source_date_epoch()?.unwrap_or_default();
+    /// let dst = File::create("release.tar.gz")?;
+    /// let mut builder = Builder::reproducible(dst, Format::TarGzip, epoch)?;
+    ///
+    /// builder.append_dir_all("release", "target/release/dist")?;
+    /// builder.finish()?;
+    /// # Ok::<(), archive_rs::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `format` cannot be written or if initializing the
+    /// compressor fails.
+    pub fn reproducible(
+        writer: W,
+        format: Format,
+        source_date_epoch: u64,
+    ) -> Result<Self> {
+        Self::create(writer, format, Some(source_date_epoch))
+    }
+
+    fn create(
+        writer: W,
+        format: Format,
+        source_date_epoch: Option<u64>,
+    ) -> Result<Self> {
+        let encoder = match format {
+            Format::Tar => Encoder::Tar(writer),
+
+            #[cfg(feature = "bzip2")]
+            Format::TarBzip2 => Encoder::Bzip2(BzEncoder::new(
+                writer,
+                bzip2::Compression::default(),
+            )),
+
+            #[cfg(feature = "flate2")]
+            Format::TarGzip => Encoder::Gzip(
+                GzBuilder::new()
+                    .mtime(0)
+                    .operating_system(UNKNOWN_OS)
+                    .write(writer, flate2::Compression::default()),
+            ),
+
+            #[cfg(feature = "lz4")]
+            Format::TarLz4 => {
+                Encoder::Lz4(lz4::EncoderBuilder::new().build(writer)?)
+            }
+
+            #[cfg(feature = "xz2")]
+            Format::TarXz => Encoder::Xz(XzEncoder::new(writer, 6)),
+
+            #[cfg(feature = "zstd")]
+            Format::TarZstd => Encoder::Zstd(ZstdEncoder::new(writer, 0)?),
+
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::UnsupportedWriteFormat(format)),
+        };
+
+        let mut inner = tar::Builder::new(encoder);
+        inner.follow_symlinks(false);
+
+        Ok(Self {
+            inner,
+            source_date_epoch,
+        })
+    }
+
+    /// Appends `entry` of another archive with its metadata and contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the entry or writing fails.
+    pub fn append_entry(&mut self, entry: &mut Entry<'_>) -> Result<()> {
+        let path = entry.path()?.into_owned();
+        self.append_entry_as(entry, path)
+    }
+
+    /// Appends `entry` of another archive with its metadata and contents at
+    /// `path` instead of its own path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the entry or writing fails.
+    pub fn append_entry_as(
+        &mut self,
+        entry: &mut Entry<'_>,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let link_name = entry.link_name()?.map(Cow::into_owned);
+        self.append(entry, path.as_ref(), link_name.as_deref())
+    }
+
+    /// Appends `entry` at `path`, pointing to `link_name` if it is a link.
+    pub(crate) fn append(
+        &mut self,
+        entry: &mut Entry<'_>,
+        path: &Path,
+        link_name: Option<&Path>,
+    ) -> Result<()> {
+        let mut header = entry.header().clone();
+        header.set_size(entry.size());
+
+        let extensions = entry.pax_extensions()?;
+
+        self.append_header(header, &extensions, path, link_name, entry)
+    }
+
+    /// Appends the hard link with `header` and the PAX extended header
+    /// records `extensions` as a regular file at `path`, with the `size`
+    /// bytes of the contents of its target read from `data`, because the
+    /// target is not written.
+    pub(crate) fn append_unlinked(
+        &mut self,
+        mut header: tar::Header,
+        extensions: &[(String, Vec<u8>)],
+        path: &Path,
+        size: u64,
+        data: impl Read,
+    ) -> Result<()> {
+        header.set_entry_type(tar::EntryType::Regular);
+        header.as_old_mut().linkname = [0; 100];
+        header.set_size(size);
+
+        self.append_header(header, extensions, path, None, data)
+    }
+
+    /// Appends an entry with `header`, the PAX extended header records
+    /// `extensions` and contents `data` at `path`, pointing to `link_name`
+    /// if it is a link.
+    ///
+    /// Records that describe the path, link target, size and sparse layout
+    /// are replaced by what is written, and records are dropped entirely in
+    /// reproducible mode.
+    pub(crate) fn append_header(
+        &mut self,
+        mut header: tar::Header,
+        extensions: &[(String, Vec<u8>)],
+        path: &Path,
+        link_name: Option<&Path>,
+        data: impl Read,
+    ) -> Result<()> {
+        let entry_type = header.entry_type();
+
+        if let Some(epoch) = self.source_date_epoch {
+            normalize_header(&mut header, epoch);
+        } else {
+            let extensions = extensions.iter().filter(|(key, _)| {
+                !matches!(key.as_str(), "path" | "linkpath" | "size")
+                    && !key.starts_with("GNU.sparse.")
+            });
+
+            self.inner.append_pax_extensions(
+                extensions
+                    .map(|(key, value)| (key.as_str(), value.as_slice())),
+            )?;
+        }
+
+        if entry_type.is_hard_link() || entry_type.is_symlink() {
+            if let Some(target) = link_name {
+                self.inner.append_link(&mut header, path, target)?;
+                return Ok(());
+            }
+        }
+
+        // sparse files are written with their holes filled in
+        if entry_type.is_gnu_sparse() {
+            header.set_entry_type(tar::EntryType::Regular);
+        }
+
+        self.inner.append_data(&mut header, path, data)?;
+
+        Ok(())
+    }
+
+    /// Appends the directory `src` and everything below it as `path`, sorted
+    /// by path.
+    ///
+    /// Symbolic links are stored as links and not followed. Hard links are
+    /// stored as separate regular files, and special files such as devices,
+    /// fifos and sockets are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading `src` or writing fails.
+    pub fn append_dir_all(
+        &mut self,
+        path: impl AsRef<Path>,
+        src: impl AsRef<Path>,
+    ) -> Result<()> {
+        self.append_path(path.as_ref(), src.as_ref())
+    }
+
+    fn append_path(&mut self, path: &Path, src: &Path) -> Result<()> {
+        let metadata = fs::symlink_metadata(src)?;
+        let file_type = metadata.file_type();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata_in_mode(&metadata, tar::HeaderMode::Complete);
+
+        if let Some(epoch) = self.source_date_epoch {
+            normalize_header(&mut header, epoch);
+        }
+
+        if file_type.is_symlink() {
+            let target = fs::read_link(src)?;
+            self.inner.append_link(&mut header, path, target)?;
+        } else if file_type.is_file() {
+            let file = File::open(src)?;
+            self.inner.append_data(&mut header, path, file)?;
+        } else if file_type.is_dir() {
+            if !path.as_os_str().is_empty() {
+                self.inner.append_data(&mut header, path, io::empty())?;
+            }
+
+            let mut children = fs::read_dir(src)?
+                .map(|child| child.map(|child| child.file_name()))
+                .collect::<io::Result<Vec<_>>>()?;
+
+            children.sort();
+
+            for name in children {
+                self.append_path(&path.join(&name), &src.join(&name))?;
+            }
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(path = %src.display(), "skipped special file");
+        }
+
+        Ok(())
+    }
+
+    /// Finishes the archive and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing the end of the archive fails.
+    pub fn finish(self) -> Result<W> {
+        Ok(self.inner.into_inner()?.finish()?)
+    }
+}
+
+/// Gzip operating system of unknown origin.
+#[cfg(feature = "flate2")]
+const UNKNOWN_OS: u8 = 255;
+
+/// Returns the value of the `SOURCE_DATE_EPOCH` environment variable, the
+/// timestamp reproducible builds clamp modification times to, see
+/// [`Builder::reproducible`].
+///
+/// # Errors
+///
+/// Returns an error if the variable is set but not a non-negative number of
+/// seconds.
+pub fn source_date_epoch() -> Result<Option<u64>> {
+    match env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::InvalidSourceDateEpoch(value)),
+
+        Err(env::VarError::NotPresent) => Ok(None),
+
+        Err(env::VarError::NotUnicode(value)) => {
+            Err(Error::InvalidSourceDateEpoch(
+                value.to_string_lossy().into_owned(),
+            ))
+        }
+    }
+}
+
+/// Fields of a header changed by [`normalize_header`].
+// ALLOW these are independent flags, not states
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Changes {
+    pub mtime: bool,
+    pub owner: bool,
+    pub mode: bool,
+    pub times: bool,
+}
+
+/// Replaces `header` with a GNU header of the same type, size and device
+/// with its modification time clamped to `epoch`, without owner and access
+/// and change times, and with normalized permissions.
+pub fn normalize_header(header: &mut tar::Header, epoch: u64) -> Changes {
+    let entry_type = header.entry_type();
+    let mode = header.mode().unwrap_or_default();
+    let mtime = header.mtime().unwrap_or(u64::MAX);
+
+    let normalized = if entry_type.is_symlink() {
+        0o777
+    } else if entry_type.is_dir() || mode & 0o100 != 0 {
+        0o755
+    } else {
+        0o644
+    };
+
+    let changes = Changes {
+        mtime: mtime > epoch,
+        owner: header.uid().map_or(true, |uid| uid != 0)
+            || header.gid().map_or(true, |gid| gid != 0)
+            || !matches!(header.username(), Ok(None | Some("")))
+            || !matches!(header.groupname(), Ok(None | Some(""))),
+        mode: mode != normalized,
+        times: header.as_gnu().is_some_and(|gnu| {
+            gnu.atime().is_ok_and(|atime| atime != 0)
+                || gnu.ctime().is_ok_and(|ctime| ctime != 0)
+        }),
+    };
+
+    let mut normalized_header = tar::Header::new_gnu();
+    normalized_header.set_entry_type(entry_type);
+    normalized_header.set_size(header.size().unwrap_or_default());
+    normalized_header.set_mode(normalized);
+    normalized_header.set_mtime(mtime.min(epoch));
+    normalized_header.set_uid(0);
+    normalized_header.set_gid(0);
+
+    if entry_type.is_character_special() || entry_type.is_block_special() {
+        if let Ok(Some(major)) = header.device_major() {
+            // devices only fail to fit if they are too large, which they
+            // were not in the original header
+            let _ = normalized_header.set_device_major(major);
+        }
+
+        if let Ok(Some(minor)) = header.device_minor() {
+            let _ = normalized_header.set_device_minor(minor);
+        }
+    }
+
+    *header = normalized_header;
+
+    changes
+}
+
+impl<W: Write> Encoder<W> {
+    // ALLOW only the plain tar writer cannot fail to finish
+    #[allow(clippy::unnecessary_wraps)]
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Tar(writer) => Ok(writer),
+
+            #[cfg(feature = "bzip2")]
+            Self::Bzip2(encoder) => encoder.finish(),
+
+            #[cfg(feature = "flate2")]
+            Self::Gzip(encoder) => encoder.finish(),
+
+            #[cfg(feature = "lz4")]
+            Self::Lz4(encoder) => {
+                let (writer, result) = encoder.finish();
+                result.map(|()| writer)
+            }
+
+            #[cfg(feature = "xz2")]
+            Self::Xz(encoder) => encoder.finish(),
+
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tar(writer) => writer.write(buf),
+
+            #[cfg(feature = "bzip2")]
+            Self::Bzip2(encoder) => encoder.write(buf),
+
+            #[cfg(feature = "flate2")]
+            Self::Gzip(encoder) => encoder.write(buf),
+
+            #[cfg(feature = "lz4")]
+            Self::Lz4(encoder) => encoder.write(buf),
+
+            #[cfg(feature = "xz2")]
+            Self::Xz(encoder) => encoder.write(buf),
+
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tar(writer) => writer.flush(),
+
+            #[cfg(feature = "bzip2")]
+            Self::Bzip2(encoder) => encoder.flush(),
+
+            #[cfg(feature = "flate2")]
+            Self::Gzip(encoder) => encoder.flush(),
+
+            #[cfg(feature = "lz4")]
+            Self::Lz4(encoder) => encoder.flush(),
+
+            #[cfg(feature = "xz2")]
+            Self::Xz(encoder) => encoder.flush(),
+
+            #[cfg(feature = "zstd")]
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Converts the archive `src` into an archive of `format` written to
+/// `writer`, re-emitting every entry with its metadata without extracting
+/// anything to disk, and returns the writer.
+///
+/// Only tarball formats are supported, so conversion changes the
+/// compression of the tarball, e.g. from `.tar.gz` to `.tar.zst`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// use archive_rs::support::Format;
+/// use archive_rs::Archive;
+///
+/// let src = Archive::open("backup.tar.gz")?;
+/// let dst = BufWriter::new(File::create("backup.tar.zst")?);
+///
+/// archive_rs::convert(src, Format::TarZstd, dst)?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `format` cannot be written, or if reading `src` or
+/// writing fails.
+pub fn convert<W: Write>(
+    mut src: Archive,
+    format: Format,
+    writer: W,
+) -> Result<W> {
+    let mut builder = Builder::new(writer, format)?;
+
+    for entry in src.entries()? {
+        builder.append_entry(&mut entry?)?;
+    }
+
+    builder.finish()
+}
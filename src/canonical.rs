@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::builder::normalize_header;
+use crate::entry::normalize;
+use crate::spool::Spool;
+use crate::support::Format;
+use crate::{Archive, Builder, Result};
+
+/// Normalizations applied by [`canonicalize`], counted in entries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Normalization {
+    /// Number of entries written.
+    pub entries: u64,
+
+    /// Whether the entries were not sorted by path.
+    pub reordered: bool,
+
+    /// Entries whose path had `.` components or a trailing `/`.
+    pub paths: u64,
+
+    /// Entries modified after the source date epoch.
+    pub mtimes: u64,
+
+    /// Entries with a non-zero owner or group, or owner or group names.
+    pub owners: u64,
+
+    /// Entries whose permissions were not `0755` or `0644`.
+    pub modes: u64,
+
+    /// Entries with access or change times.
+    pub times: u64,
+
+    /// Entries with PAX extended headers, which are not written.
+    pub extended_headers: u64,
+}
+
+impl Normalization {
+    /// Returns true if the archive already was in canonical form, i.e. only
+    /// the compression may have changed.
+    #[must_use]
+    pub const fn is_canonical(&self) -> bool {
+        !self.reordered
+            && self.paths == 0
+            && self.mtimes == 0
+            && self.owners == 0
+            && self.modes == 0
+            && self.times == 0
+            && self.extended_headers == 0
+    }
+}
+
+/// Entry whose contents have been spooled.
+struct Record {
+    path: PathBuf,
+    header: tar::Header,
+    link_name: Option<PathBuf>,
+    offset: u64,
+
+    /// Index of the entry that holds the contents of a hard link.
+    holder: Option<usize>,
+}
+
+/// Rewrites the archive `src` in canonical form as an archive of `format` to
+/// `writer`, and returns the writer and the normalizations applied.
+///
+/// Archives with the same contents and metadata become byte-identical.
+/// Entries are sorted by path and written like with
+/// [`Builder::reproducible`] with `source_date_epoch`, without PAX extended
+/// headers and with `.` components removed from paths. Of a file and its
+/// hard links, the one that sorts first holds the contents and the others
+/// link to it.
+///
+/// To sort the entries, their contents are spooled to an anonymous temporary
+/// file, which needs as much space as the uncompressed archive.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// use archive_rs::support::Format;
+/// use archive_rs::Archive;
+///
+/// let src = Archive::open("vendor.tar.gz")?;
+/// let dst = BufWriter::new(File::create("vendor.tar.zst")?);
+///
+/// let (_, normalization) =
+///     archive_rs::canonicalize(src, Format::TarZstd, dst, 0)?;
+///
+/// if !normalization.is_canonical() {
+///     println!("{normalization:?}");
+/// }
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `format` cannot be written, or if reading `src`,
+/// spooling or writing fails.
+pub fn canonicalize<W: Write>(
+    mut src: Archive,
+    format: Format,
+    writer: W,
+    source_date_epoch: u64,
+) -> Result<(W, Normalization)> {
+    let mut builder =
+        Builder::reproducible(writer, format, source_date_epoch)?;
+    let mut normalization = Normalization::default();
+
+    let mut spool = Spool::new()?;
+    let mut spooled: Vec<Record> = vec![];
+    let mut holders: HashMap<PathBuf, usize> = HashMap::new();
+
+    for entry in src.entries()? {
+        let mut entry = entry?;
+
+        let raw = entry.path()?.into_owned();
+        let path = normalize(&raw);
+
+        if path.as_os_str().len() != raw.as_os_str().len() {
+            normalization.paths += 1;
+        }
+
+        if !entry.pax_extensions()?.is_empty() {
+            normalization.extended_headers += 1;
+        }
+
+        let mut header = entry.header().clone();
+        header.set_size(entry.size());
+
+        let changes = normalize_header(&mut header, source_date_epoch);
+        normalization.mtimes += u64::from(changes.mtime);
+        normalization.owners += u64::from(changes.owner);
+        normalization.modes += u64::from(changes.mode);
+        normalization.times += u64::from(changes.times);
+
+        let link_name = entry.link_name()?.map(|target| normalize(&target));
+        let index = spooled.len();
+
+        let holder = if header.entry_type().is_hard_link() {
+            link_name
+                .as_ref()
+                .and_then(|target| holders.get(target).copied())
+        } else {
+            None
+        };
+
+        holders.insert(path.clone(), holder.unwrap_or(index));
+
+        let offset = spool.push(&mut entry)?;
+
+        spooled.push(Record {
+            path,
+            header,
+            link_name,
+            offset,
+            holder,
+        });
+    }
+
+    let mut spool = spool.finish()?;
+
+    let mut order: Vec<usize> = (0..spooled.len()).collect();
+    order.sort_by(|a, b| spooled[*a].path.cmp(&spooled[*b].path));
+    normalization.reordered = order.iter().enumerate().any(|(i, j)| i != *j);
+
+    // paths at which the contents of each holder have been written
+    let mut written: HashMap<usize, PathBuf> = HashMap::new();
+
+    for index in order {
+        let entry = &spooled[index];
+        let is_hard_link = entry.header.entry_type().is_hard_link();
+
+        let holder = match entry.holder {
+            Some(holder) => holder,
+            None if !is_hard_link => index,
+
+            // the target of the link is not in the archive
+            None => {
+                builder.append_header(
+                    entry.header.clone(),
+                    &[],
+                    &entry.path,
+                    entry.link_name.as_deref(),
+                    io::empty(),
+                )?;
+
+                normalization.entries += 1;
+                continue;
+            }
+        };
+
+        if let Some(target) = written.get(&holder) {
+            // the contents have been written by a link that sorts earlier
+            let mut header = entry.header.clone();
+            header.set_entry_type(tar::EntryType::Link);
+            header.set_size(0);
+
+            builder.append_header(
+                header,
+                &[],
+                &entry.path,
+                Some(target),
+                io::empty(),
+            )?;
+        } else {
+            let contents = &spooled[holder];
+            let data = spool.read(contents.offset, contents.header.size()?)?;
+
+            builder.append_header(
+                contents.header.clone(),
+                &[],
+                &entry.path,
+                contents.link_name.as_deref(),
+                data,
+            )?;
+
+            written.insert(holder, entry.path.clone());
+        }
+
+        normalization.entries += 1;
+    }
+
+    Ok((builder.finish()?, normalization))
+}
@@ -0,0 +1,497 @@
+//! Differences between archives, and between archives and directories.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use archive_rs::diff;
+//! use archive_rs::{Algorithm, Archive};
+//!
+//! let mut old = Archive::open("release-1.0.tar.gz")?;
+//! let mut new = Archive::open("release-1.0.tar.zst")?;
+//!
+//! let diff = diff::compare(&mut old, &mut new, Algorithm::Sha256)?;
+//!
+//! for modified in &diff.modified {
+//!     println!("{}: {:?}", modified.path.display(), modified.fields);
+//! }
+//! # Ok::<(), archive_rs::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+#[cfg(feature = "glob")]
+use std::fs::{self, File, Metadata};
+#[cfg(feature = "glob")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "glob")]
+use std::time::UNIX_EPOCH;
+
+#[cfg(feature = "glob")]
+use globset::GlobSet;
+
+use crate::entry::normalize;
+use crate::{digest, Algorithm, Archive, Entry, EntryType, Result};
+
+/// Differences between two archives, see [`compare`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diff {
+    /// Paths of the entries that are only in the new archive.
+    pub added: Vec<PathBuf>,
+
+    /// Paths of the entries that are only in the old archive.
+    pub removed: Vec<PathBuf>,
+
+    /// Entries that are in both archives but differ.
+    pub modified: Vec<Modified>,
+}
+
+impl Diff {
+    /// Returns true if there are no differences.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+/// Entry that differs, see [`Diff::modified`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Modified {
+    /// Path of the entry.
+    pub path: PathBuf,
+
+    /// Fields that differ.
+    pub fields: Vec<Field>,
+}
+
+impl Modified {
+    /// Returns true if only metadata differs, but not the contents.
+    #[must_use]
+    pub fn is_metadata_only(&self) -> bool {
+        !self.fields.contains(&Field::Content)
+    }
+}
+
+/// Field of an entry that differs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+// NONEXHAUSTIVE new formats could compare more fields
+#[non_exhaustive]
+pub enum Field {
+    /// File type, like regular file or directory.
+    Type,
+
+    /// Contents of a regular file, compared by size and digest.
+    Content,
+
+    /// Permission bits.
+    Mode,
+
+    /// Numeric user or group ID of the owner.
+    Owner,
+
+    /// Modification time.
+    Mtime,
+
+    /// Target of a hard or symbolic link.
+    LinkTarget,
+}
+
+/// Metadata and digest of an entry or a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Summary {
+    entry_type: EntryType,
+    size: u64,
+    digest: Option<String>,
+    mode: u32,
+    uid: u64,
+    gid: u64,
+    mtime: u64,
+    link_name: Option<PathBuf>,
+}
+
+impl Summary {
+    /// Returns the summary of `entry`, hashing it with `algorithm` if it is
+    /// a regular file, sparse files are summarized as the regular files they
+    /// are extracted to.
+    fn of(entry: &mut Entry<'_>, algorithm: Algorithm) -> Result<Self> {
+        let entry_type = entry.entry_type().extracted();
+
+        let digest = if entry_type.is_file() {
+            let mut digests = digest(&mut *entry, &[algorithm])?;
+            digests.remove(&algorithm)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            entry_type,
+            size: entry.size(),
+            digest,
+            mode: entry.mode()?,
+            uid: entry.uid()?,
+            gid: entry.gid()?,
+            mtime: entry.mtime()?,
+            link_name: entry.link_name()?.map(|path| normalize(&path)),
+        })
+    }
+
+    /// Returns the summary of the file at `path`, without following
+    /// symbolic links, hashing it with `algorithm` if it is a regular file.
+    #[cfg(feature = "glob")]
+    fn of_file(path: &Path, algorithm: Algorithm) -> Result<Self> {
+        let metadata = fs::symlink_metadata(path)?;
+        let entry_type = file_type(&metadata);
+
+        let digest = if entry_type.is_file() {
+            let mut digests = digest(File::open(path)?, &[algorithm])?;
+            digests.remove(&algorithm)
+        } else {
+            None
+        };
+
+        let link_name = if entry_type == EntryType::Symlink {
+            Some(normalize(&fs::read_link(path)?))
+        } else {
+            None
+        };
+
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |mtime| mtime.as_secs());
+
+        let (mode, uid, gid) = owner_and_mode(&metadata);
+
+        Ok(Self {
+            entry_type,
+            size: if entry_type.is_file() {
+                metadata.len()
+            } else {
+                0
+            },
+            digest,
+            mode,
+            uid,
+            gid,
+            mtime,
+            link_name,
+        })
+    }
+
+    /// Returns the fields that differ from `other`.
+    fn fields(&self, other: &Self) -> Vec<Field> {
+        let mut fields = vec![];
+
+        if self.entry_type != other.entry_type {
+            fields.push(Field::Type);
+        }
+
+        if self.size != other.size || self.digest != other.digest {
+            fields.push(Field::Content);
+        }
+
+        if self.mode & 0o7777 != other.mode & 0o7777 {
+            fields.push(Field::Mode);
+        }
+
+        if self.uid != other.uid || self.gid != other.gid {
+            fields.push(Field::Owner);
+        }
+
+        if self.mtime != other.mtime {
+            fields.push(Field::Mtime);
+        }
+
+        if self.link_name != other.link_name {
+            fields.push(Field::LinkTarget);
+        }
+
+        fields
+    }
+}
+
+/// Returns the differences between the entries of the `old` and the `new`
+/// archive, which may be of different formats.
+///
+/// Entries are matched by path, without leading `./`, and contents of
+/// regular files are compared by their digests of `algorithm`. If a path
+/// occurs multiple times in an archive, the last entry counts, as it would
+/// when extracting the archive.
+///
+/// # Errors
+///
+/// Returns an error if reading either archive fails.
+pub fn compare(
+    old: &mut Archive,
+    new: &mut Archive,
+    algorithm: Algorithm,
+) -> Result<Diff> {
+    let mut old = summaries(old, algorithm)?;
+    let new = summaries(new, algorithm)?;
+
+    let mut diff = Diff::default();
+
+    for (path, new) in new {
+        let Some(old) = old.remove(&path) else {
+            diff.added.push(path);
+            continue;
+        };
+
+        let fields = old.fields(&new);
+
+        if !fields.is_empty() {
+            diff.modified.push(Modified { path, fields });
+        }
+    }
+
+    diff.removed = old.into_keys().collect();
+
+    Ok(diff)
+}
+
+fn summaries(
+    archive: &mut Archive,
+    algorithm: Algorithm,
+) -> Result<BTreeMap<PathBuf, Summary>> {
+    let mut summaries = BTreeMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        let path = normalize(&entry.path()?);
+
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+
+        let summary = Summary::of(&mut entry, algorithm)?;
+        summaries.insert(path, summary);
+    }
+
+    Ok(summaries)
+}
+
+/// Returns the differences between the entries of `archive` and the files
+/// in `dir`, e.g. to detect drift after extracting the archive.
+///
+/// The archive is compared like the old and the directory like the new
+/// archive of [`compare`]: [`Diff::added`] are files that are only on disk,
+/// [`Diff::removed`] are entries that are missing on disk. Ownership is not
+/// compared, because it depends on the user who extracted the archive,
+/// neither are the modification times of directories, which extracting does
+/// not restore, and hard links are compared as regular files without their
+/// contents. Of modes, only the permission bits `0o777` are compared,
+/// because extracting drops the setuid, setgid and sticky bits.
+///
+/// Paths that match `ignore`, or whose parent directories match it, are
+/// skipped in both the archive and the directory.
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::diff;
+/// use archive_rs::{Algorithm, Archive};
+/// use globset::{Glob, GlobSetBuilder};
+///
+/// let ignore = GlobSetBuilder::new().add(Glob::new("*.log")?).build()?;
+///
+/// let mut archive = Archive::open("release-1.0.tar.zst")?;
+/// let diff = diff::compare_with_dir(
+///     &mut archive,
+///     "/srv/release",
+///     Algorithm::Sha256,
+///     Some(&ignore),
+/// )?;
+///
+/// if !diff.is_empty() {
+///     eprintln!("/srv/release has drifted");
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if reading the archive or the directory fails.
+#[cfg(feature = "glob")]
+pub fn compare_with_dir(
+    archive: &mut Archive,
+    dir: impl AsRef<Path>,
+    algorithm: Algorithm,
+    ignore: Option<&GlobSet>,
+) -> Result<Diff> {
+    let ignored = |path: &Path| {
+        ignore.is_some_and(|ignore| {
+            path.ancestors()
+                .filter(|path| !path.as_os_str().is_empty())
+                .any(|path| ignore.is_match(path))
+        })
+    };
+
+    let mut expected = summaries(archive, algorithm)?;
+    expected.retain(|path, _| !ignored(path));
+
+    let dir = dir.as_ref();
+    let mut diff = Diff::default();
+
+    let mut dirs = vec![PathBuf::new()];
+
+    while let Some(current) = dirs.pop() {
+        for file in fs::read_dir(dir.join(&current))? {
+            let path = current.join(file?.file_name());
+
+            if ignored(&path) {
+                continue;
+            }
+
+            let mut actual = Summary::of_file(&dir.join(&path), algorithm)?;
+            actual.mode &= 0o777;
+
+            if actual.entry_type == EntryType::Directory {
+                dirs.push(path.clone());
+            }
+
+            let Some(mut expected) = expected.remove(&path) else {
+                diff.added.push(path);
+                continue;
+            };
+
+            expected.mode &= 0o777;
+
+            let fields: Vec<Field> = expected
+                .fields(&actual)
+                .into_iter()
+                .filter(|field| match field {
+                    Field::Owner => false,
+                    Field::Mode => actual.entry_type != EntryType::Symlink,
+                    Field::Mtime => actual.entry_type != EntryType::Directory,
+                    Field::Type | Field::Content => {
+                        expected.entry_type != EntryType::Link
+                            || !actual.entry_type.is_file()
+                    }
+                    _ => true,
+                })
+                .collect();
+
+            if !fields.is_empty() {
+                diff.modified.push(Modified { path, fields });
+            }
+        }
+    }
+
+    diff.added.sort_unstable();
+    diff.modified.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    diff.removed = expected.into_keys().collect();
+
+    Ok(diff)
+}
+
+#[cfg(feature = "glob")]
+fn file_type(metadata: &Metadata) -> EntryType {
+    let file_type = metadata.file_type();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_char_device() {
+            return EntryType::Char;
+        } else if file_type.is_block_device() {
+            return EntryType::Block;
+        } else if file_type.is_fifo() {
+            return EntryType::Fifo;
+        }
+    }
+
+    if file_type.is_dir() {
+        EntryType::Directory
+    } else if file_type.is_symlink() {
+        EntryType::Symlink
+    } else if file_type.is_file() {
+        EntryType::Regular
+    } else {
+        EntryType::Other
+    }
+}
+
+#[cfg(all(unix, feature = "glob"))]
+fn owner_and_mode(metadata: &Metadata) -> (u32, u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    (
+        metadata.mode() & 0o7777,
+        u64::from(metadata.uid()),
+        u64::from(metadata.gid()),
+    )
+}
+
+#[cfg(all(not(unix), feature = "glob"))]
+fn owner_and_mode(metadata: &Metadata) -> (u32, u64, u64) {
+    let mode = if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    };
+
+    (mode, 0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const SIZE: u64 = 8192;
+    // GNU tar ends the map with an empty region at the end of the file
+    const REGIONS: &[(u64, &[u8])] =
+        &[(0, &[b'h'; 512]), (6144, b"tail"), (SIZE, b"")];
+
+    #[test]
+    fn sparse_files_equal_regular_files() {
+        let sparse = testing::tarball(|builder| {
+            testing::sparse(builder, "file", SIZE, REGIONS);
+        });
+
+        let regular = testing::tarball(|builder| {
+            testing::file(builder, "file", &testing::expand(SIZE, REGIONS));
+        });
+
+        let (_old, mut old) = testing::open(&sparse, ".tar");
+        let (_new, mut new) = testing::open(&regular, ".tar");
+
+        let diff = compare(&mut old, &mut new, Algorithm::all()[0]).unwrap();
+        assert!(diff.is_empty(), "{diff:?}");
+    }
+
+    #[test]
+    #[cfg(feature = "glob")]
+    fn extracted_archive_has_not_drifted() {
+        let tarball = testing::tarball(|builder| {
+            testing::entry(builder, tar::EntryType::Directory, "./", b"");
+            testing::sparse(builder, "./sparse", SIZE, REGIONS);
+
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o4755);
+            header.set_uid(1000);
+            header.set_gid(1000);
+            header.set_mtime(1_700_000_000);
+            header.set_size(7);
+            builder
+                .append_data(&mut header, "./setuid", &b"#!/bin/"[..])
+                .unwrap();
+        });
+
+        let (_file, mut archive) = testing::open(&tarball, ".tar");
+        let dir = tempfile::tempdir().unwrap();
+        archive.unpack(dir.path()).unwrap();
+
+        let mut archive = archive.reopen().unwrap();
+        let algorithm = Algorithm::all()[0];
+
+        let diff = compare_with_dir(&mut archive, dir.path(), algorithm, None)
+            .unwrap();
+        assert!(diff.is_empty(), "{diff:?}");
+    }
+}
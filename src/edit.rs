@@ -0,0 +1,279 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use tempfile::NamedTempFile;
+
+use crate::entry::normalize;
+use crate::{Archive, Builder, EntryType, Error, Result};
+
+/// Edits of the entries of an archive, applied by rewriting it.
+///
+/// Entries are identified by their path in the source archive, without
+/// leading `./`. Everything that is not edited is kept as it is, including
+/// PAX extended headers, and the archive keeps its compression format.
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::{Archive, Edit};
+///
+/// let src = Archive::open("release-1.0.tar.gz")?;
+///
+/// Edit::new()
+///     .delete("config/secrets.toml")
+///     .rename("README", "README.md")
+///     .chmod("bin/tool", 0o755)
+///     .apply(src, "release-1.0.tar.gz")?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+#[derive(Default)]
+#[must_use]
+pub struct Edit {
+    changes: BTreeMap<PathBuf, Change>,
+}
+
+/// Edits of a single entry.
+#[derive(Default)]
+struct Change {
+    delete: bool,
+    rename: Option<PathBuf>,
+    contents: Option<Box<dyn Read>>,
+    mode: Option<u32>,
+    owner: Option<(u64, u64)>,
+    applied: bool,
+}
+
+impl Edit {
+    /// Returns an empty set of edits.
+    pub const fn new() -> Self {
+        Self {
+            changes: BTreeMap::new(),
+        }
+    }
+
+    /// Deletes the entry at `path`.
+    ///
+    /// Hard links to the entry are deleted with it, so that no copy of the
+    /// contents, e.g. of a leaked secret, is left in the archive.
+    pub fn delete(mut self, path: impl AsRef<Path>) -> Self {
+        self.change(path).delete = true;
+        self
+    }
+
+    /// Moves the entry at `path` to `to`.
+    ///
+    /// Hard links to the entry are updated to point to `to`.
+    pub fn rename(
+        mut self,
+        path: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> Self {
+        self.change(path).rename = Some(normalize(to.as_ref()));
+        self
+    }
+
+    /// Replaces the contents of the regular file at `path` with everything
+    /// read from `contents`.
+    pub fn replace(
+        mut self,
+        path: impl AsRef<Path>,
+        contents: impl Read + 'static,
+    ) -> Self {
+        self.change(path).contents = Some(Box::new(contents));
+        self
+    }
+
+    /// Changes the permissions of the entry at `path` to `mode`.
+    pub fn chmod(mut self, path: impl AsRef<Path>, mode: u32) -> Self {
+        self.change(path).mode = Some(mode);
+        self
+    }
+
+    /// Changes the owner and group of the entry at `path` to `uid` and
+    /// `gid`, removing the owner and group names.
+    pub fn chown(
+        mut self,
+        path: impl AsRef<Path>,
+        uid: u64,
+        gid: u64,
+    ) -> Self {
+        self.change(path).owner = Some((uid, gid));
+        self
+    }
+
+    fn change(&mut self, path: impl AsRef<Path>) -> &mut Change {
+        self.changes.entry(normalize(path.as_ref())).or_default()
+    }
+
+    /// Writes `src` with these edits applied to `dst`, in the format of
+    /// `src`.
+    ///
+    /// The edited archive is written to a temporary file next to `dst`,
+    /// which replaces `dst` only once it is complete, so `dst` may be the
+    /// file `src` was opened from. An existing `dst` keeps its permissions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading `src` or writing fails, or if there is no
+    /// entry for one of the edited paths, in which case `dst` is left
+    /// untouched.
+    pub fn apply(
+        mut self,
+        mut src: Archive,
+        dst: impl AsRef<Path>,
+    ) -> Result<()> {
+        let dst = dst.as_ref();
+        let dir = match dst.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let temp = NamedTempFile::new_in(dir)?;
+
+        if let Ok(metadata) = fs::metadata(dst) {
+            temp.as_file().set_permissions(metadata.permissions())?;
+        }
+
+        let mut builder = Builder::new(BufWriter::new(temp), src.format())?;
+        let mut deleted = HashSet::new();
+
+        for entry in src.entries()? {
+            let mut entry = entry?;
+            let raw = entry.path()?.into_owned();
+            let path = normalize(&raw);
+
+            let mut header = entry.header().clone();
+            header.set_size(entry.size());
+
+            let mut extensions = entry.pax_extensions()?;
+            let mut link_name = entry.link_name()?.map(Cow::into_owned);
+
+            if entry.entry_type() == EntryType::Link {
+                if let Some(target) = &link_name {
+                    let target = normalize(target);
+
+                    if deleted.contains(&target) {
+                        continue;
+                    }
+
+                    if let Some(to) = self
+                        .changes
+                        .get(&target)
+                        .and_then(|change| change.rename.clone())
+                    {
+                        link_name = Some(to);
+                    }
+                }
+            }
+
+            let Some(change) = self.changes.get_mut(&path) else {
+                builder.append_header(
+                    header,
+                    &extensions,
+                    &raw,
+                    link_name.as_deref(),
+                    &mut entry,
+                )?;
+
+                continue;
+            };
+
+            change.applied = true;
+
+            if change.delete {
+                deleted.insert(path);
+                continue;
+            }
+
+            if let Some(mode) = change.mode {
+                header.set_mode(mode);
+            }
+
+            if let Some((uid, gid)) = change.owner {
+                header.set_uid(uid);
+                header.set_gid(gid);
+
+                // names only fail to fit if they are too long, which empty
+                // ones are not
+                let _ = header.set_username("");
+                let _ = header.set_groupname("");
+
+                extensions.retain(|(key, _)| {
+                    !matches!(key.as_str(), "uid" | "gid" | "uname" | "gname")
+                });
+            }
+
+            let path = change.rename.clone().unwrap_or(raw);
+
+            if let Some(contents) = &mut change.contents {
+                let mut spool = tempfile::tempfile()?;
+                let size = io::copy(contents, &mut spool)?;
+                spool.rewind()?;
+
+                header.set_size(size);
+
+                builder.append_header(
+                    header,
+                    &extensions,
+                    &path,
+                    link_name.as_deref(),
+                    spool,
+                )?;
+            } else {
+                builder.append_header(
+                    header,
+                    &extensions,
+                    &path,
+                    link_name.as_deref(),
+                    &mut entry,
+                )?;
+            }
+        }
+
+        if let Some((path, _)) =
+            self.changes.iter().find(|(_, change)| !change.applied)
+        {
+            return Err(Error::EntryNotFound(path.clone()));
+        }
+
+        let mut writer = builder.finish()?;
+        writer.flush()?;
+
+        let temp = writer.into_inner().map_err(io::Error::from)?;
+        temp.as_file().sync_all()?;
+        temp.persist(dst).map_err(|e| e.error)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tar::EntryType;
+
+    use super::*;
+    use crate::testing::{self, Listed};
+
+    #[test]
+    fn deletes_hard_links_with_their_target() {
+        let tarball = testing::tarball(|builder| {
+            testing::file(builder, "secret", b"secret");
+            testing::link(builder, EntryType::Link, "copy", "secret");
+            testing::file(builder, "kept", b"kept");
+        });
+
+        let (file, src) = testing::open(&tarball, ".tar");
+        Edit::new()
+            .delete("secret")
+            .apply(src, file.path())
+            .unwrap();
+
+        assert_eq!(
+            testing::list(&fs::read(file.path()).unwrap()),
+            [Listed::new("kept", EntryType::Regular, b"kept", None)]
+        );
+    }
+}
@@ -1,14 +1,15 @@
-#[cfg(feature = "tar")]
-use std::fs::File;
+use std::collections::HashMap;
+use std::io;
 #[cfg(feature = "zstd")]
 use std::io::BufReader;
+use std::path::PathBuf;
 
 #[cfg(feature = "bzip2")]
 use bzip2::read::BzDecoder as BzSysDecoder;
 #[cfg(feature = "bzip2-rs")]
 use bzip2_rs::decoder::DecoderReader as BzNativeDecoder;
 #[cfg(feature = "flate2")]
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 #[cfg(feature = "lz4")]
 use lz4::Decoder as Lz4Decoder;
 #[cfg(feature = "xz2")]
@@ -16,8 +17,34 @@ use xz2::read::XzDecoder;
 #[cfg(feature = "zstd")]
 use zstd::stream::read::Decoder as ZstdDecoder;
 
+#[cfg(feature = "glob")]
+use globset::GlobSet;
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::archive::{self, Observer};
+use crate::entry::normalize;
+use crate::filter::Filter;
+#[cfg(all(
+    feature = "tar",
+    any(feature = "flate2", feature = "xz2", feature = "zstd")
+))]
+use crate::parallel::Parallel;
+#[cfg(feature = "tar")]
+use crate::read_ahead::ReadAhead;
+#[cfg(all(feature = "tar", any(feature = "xz2", feature = "zstd")))]
+use crate::seekable::Seekable;
+use crate::source::{Source, State, Tracked, BLOCK};
+#[cfg(feature = "tar")]
+use crate::sparse::Sparse;
+#[cfg(feature = "tar")]
+use crate::spool::Spool;
 use crate::Archive;
 use crate::Entry;
+use crate::EntryType;
+use crate::Error;
+use crate::OpenOptions;
+use crate::Progress;
 use crate::Result;
 
 impl Archive {
@@ -26,88 +53,274 @@ impl Archive {
     /// # Errors
     ///
     /// Returns an error if reading the archive fails.
-    pub fn entries(&mut self) -> Result<Entries> {
-        match self {
+    pub fn entries(&mut self) -> Result<Entries<'_>> {
+        let inner = match &mut self.inner {
             #[cfg(feature = "tar")]
-            Self::Tar(archive) => {
+            archive::Inner::Tar(archive) => {
                 let entries = archive.entries()?;
-                Ok(Entries::Tar(entries))
+                Inner::Tar(entries)
             }
 
             #[cfg(all(feature = "bzip2", feature = "tar"))]
-            Self::TarBzip2(archive) => {
+            archive::Inner::TarBzip2(archive) => {
                 let entries = archive.entries()?;
-                Ok(Entries::TarBzip2(entries))
+                Inner::TarBzip2(entries)
             }
 
             #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-            Self::TarBzip2Rs(archive) => {
+            archive::Inner::TarBzip2Rs(archive) => {
                 let entries = archive.entries()?;
-                Ok(Entries::TarBzip2Rs(entries))
+                Inner::TarBzip2Rs(entries)
             }
 
             #[cfg(all(feature = "flate2", feature = "tar"))]
-            Self::TarGzip(archive) => {
+            archive::Inner::TarGzip(archive) => {
                 let entries = archive.entries()?;
-                Ok(Entries::TarGzip(entries))
+                Inner::TarGzip(entries)
             }
 
             #[cfg(all(feature = "lz4", feature = "tar"))]
-            Self::TarLz4(archive) => {
+            archive::Inner::TarLz4(archive) => {
                 let entries = archive.entries()?;
-                Ok(Entries::TarLz4(entries))
+                Inner::TarLz4(entries)
             }
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
-            Self::TarXz(archive) => {
+            archive::Inner::TarXz(archive) => {
                 let entries = archive.entries()?;
-                Ok(Entries::TarXz(entries))
+                Inner::TarXz(entries)
+            }
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            archive::Inner::TarXzSeekable(archive) => {
+                let (entries, base) = archive.entries()?;
+                Inner::TarXzSeekable(entries, base)
             }
 
             #[cfg(all(feature = "zstd", feature = "tar"))]
-            Self::TarZstd(archive) => {
+            archive::Inner::TarZstd(archive) => {
                 let entries = archive.entries()?;
-                Ok(Entries::TarZstd(entries))
+                Inner::TarZstd(entries)
             }
-        }
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            archive::Inner::TarZstdSeekable(archive) => {
+                let (entries, base) = archive.entries()?;
+                Inner::TarZstdSeekable(entries, base)
+            }
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            archive::Inner::TarParallel(archive) => {
+                let entries = archive.entries()?;
+                Inner::TarParallel(entries)
+            }
+
+            #[cfg(feature = "tar")]
+            archive::Inner::TarReadAhead(archive) => {
+                let entries = archive.entries()?;
+                Inner::TarReadAhead(entries)
+            }
+        };
+
+        Ok(Entries {
+            inner,
+            state: &self.state,
+            observer: self.observer.as_deref_mut(),
+            count: 0,
+            done: false,
+            filters: vec![],
+            paths: &self.paths,
+            options: &self.options,
+            hard_links: None,
+            next: 0,
+        })
     }
 }
 
 /// Iterator over archive entries.
 #[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct Entries<'a> {
+    inner: Inner<'a>,
+    state: &'a State,
+    observer: Option<&'a mut Observer>,
+    count: u64,
+    done: bool,
+    filters: Vec<Filter>,
+    paths: &'a [PathBuf],
+    options: &'a OpenOptions,
+
+    /// Contents of the entries at each path, if hard links are resolved.
+    hard_links: Option<HardLinks>,
+
+    /// Position of the headers of the next entry in the tarball, from which
+    /// on they are recorded while reading them.
+    next: u64,
+}
+
+impl Entries<'_> {
+    /// Returns only the entries whose paths match `globs`, see
+    /// [`Wildcards`](crate::Wildcards).
+    ///
+    /// Paths are matched without leading `./` and trailing `/`. Entries whose
+    /// paths cannot be parsed are returned as errors.
+    #[cfg(feature = "glob")]
+    pub fn matching(mut self, globs: GlobSet) -> Self {
+        self.filters.push(Filter::Matching(globs));
+        self
+    }
+
+    /// Returns only the entries whose paths do not match `globs`, like
+    /// [`Entries::matching`].
+    #[cfg(feature = "glob")]
+    pub fn excluding(mut self, globs: GlobSet) -> Self {
+        self.filters.push(Filter::Excluding(globs));
+        self
+    }
+
+    /// Returns only the entries whose paths match `regex`, like
+    /// [`Entries::matching`].
+    #[cfg(feature = "regex")]
+    pub fn matching_regex(mut self, regex: Regex) -> Self {
+        self.filters.push(Filter::MatchingRegex(regex));
+        self
+    }
+
+    /// Returns only the entries whose paths do not match `regex`, like
+    /// [`Entries::matching`].
+    #[cfg(feature = "regex")]
+    pub fn excluding_regex(mut self, regex: Regex) -> Self {
+        self.filters.push(Filter::ExcludingRegex(regex));
+        self
+    }
+
+    /// Returns hard links with the contents of their target, which is an
+    /// earlier entry of the archive.
+    ///
+    /// Hard links keep their [`EntryType::Link`], but their size and what is
+    /// read from them are those of the target. Since any file could be the
+    /// target of a later hard link, the contents of all files are spooled to
+    /// an anonymous temporary file while iterating, and files are read from
+    /// there. Seekable archives only spool the targets, which are read from
+    /// the archive opened again, see [`Archive::read_entry_at`]. Hard links
+    /// whose target is missing or follows them are returned as
+    /// [`Error::HardLinkTarget`].
+    pub fn resolve_hard_links(mut self) -> Self {
+        self.hard_links = Some(HardLinks {
+            contents: HashMap::new(),
+            spool: None,
+            lookup: None,
+        });
+
+        self
+    }
+}
+
+/// Contents of the entries that hard links can point to, see
+/// [`Entries::resolve_hard_links`].
+struct HardLinks {
+    contents: HashMap<PathBuf, Contents>,
+
+    /// Spool of the contents, created once needed.
+    spool: Option<Spool>,
+
+    /// Seekable archive opened again to read targets from, once needed.
+    lookup: Option<Archive>,
+}
+
+impl HardLinks {
+    /// Returns the spool, creating it if needed.
+    fn spool(&mut self) -> io::Result<&mut Spool> {
+        let spool = match self.spool.take() {
+            Some(spool) => spool,
+            None => Spool::new()?,
+        };
+
+        Ok(self.spool.insert(spool))
+    }
+
+    /// Spools the contents of the entry at `offset` of the archive at
+    /// `paths`, and returns their offset in the spool and size.
+    fn spool_entry_at(
+        &mut self,
+        offset: u64,
+        paths: &[PathBuf],
+        options: &OpenOptions,
+    ) -> Result<(u64, u64)> {
+        let mut archive = match self.lookup.take() {
+            Some(archive) => archive,
+            None => Archive::open_with(paths, options)?,
+        };
+
+        let mut entry = archive
+            .read_entry_at(offset)?
+            .ok_or(Error::InvalidOffset(offset))?;
+
+        let size = entry.size();
+        let spooled = self.spool()?.push(&mut entry)?;
+
+        drop(entry);
+        self.lookup = Some(archive);
+
+        Ok((spooled, size))
+    }
+}
+
+/// Where the contents of an entry are.
+#[derive(Clone, Copy)]
+enum Contents {
+    /// Offset of the entry in a seekable archive.
+    Archive(u64),
+
+    /// Offset and size in the spool.
+    Spooled(u64, u64),
+}
+
+/// Iterator over the archive entries of a specific format.
 // NONEXHAUSTIVE new formats could add new types
 #[non_exhaustive]
-pub enum Entries<'a> {
+pub enum Inner<'a> {
     #[cfg(feature = "tar")]
-    #[doc(hidden)]
-    Tar(tar::Entries<'a, File>),
+    Tar(tar::Entries<'a, Tracked<Source>>),
 
     #[cfg(all(feature = "bzip2", feature = "tar"))]
-    #[doc(hidden)]
-    TarBzip2(tar::Entries<'a, BzSysDecoder<File>>),
+    TarBzip2(tar::Entries<'a, Tracked<BzSysDecoder<Source>>>),
 
     #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-    #[doc(hidden)]
-    TarBzip2Rs(tar::Entries<'a, BzNativeDecoder<File>>),
+    TarBzip2Rs(tar::Entries<'a, Tracked<BzNativeDecoder<Source>>>),
 
     #[cfg(all(feature = "flate2", feature = "tar"))]
-    #[doc(hidden)]
-    TarGzip(tar::Entries<'a, GzDecoder<File>>),
+    TarGzip(tar::Entries<'a, Tracked<MultiGzDecoder<Source>>>),
 
     #[cfg(all(feature = "lz4", feature = "tar"))]
-    #[doc(hidden)]
-    TarLz4(tar::Entries<'a, Lz4Decoder<File>>),
+    TarLz4(tar::Entries<'a, Tracked<Lz4Decoder<Source>>>),
+
+    #[cfg(all(feature = "xz2", feature = "tar"))]
+    TarXz(tar::Entries<'a, Tracked<XzDecoder<Source>>>),
 
     #[cfg(all(feature = "xz2", feature = "tar"))]
-    #[doc(hidden)]
-    TarXz(tar::Entries<'a, XzDecoder<File>>),
+    TarXzSeekable(tar::Entries<'a, Tracked<Seekable>>, u64),
 
     #[cfg(all(feature = "zstd", feature = "tar"))]
-    #[doc(hidden)]
-    TarZstd(tar::Entries<'a, ZstdDecoder<'static, BufReader<File>>>),
+    TarZstd(
+        tar::Entries<'a, Tracked<ZstdDecoder<'static, BufReader<Source>>>>,
+    ),
+
+    #[cfg(all(feature = "zstd", feature = "tar"))]
+    TarZstdSeekable(tar::Entries<'a, Tracked<Seekable>>, u64),
+
+    #[cfg(all(
+        feature = "tar",
+        any(feature = "flate2", feature = "xz2", feature = "zstd")
+    ))]
+    TarParallel(tar::Entries<'a, Tracked<Parallel>>),
+
+    #[cfg(feature = "tar")]
+    TarReadAhead(tar::Entries<'a, Tracked<ReadAhead>>),
 
     #[cfg(not(feature = "tar"))]
-    #[doc(hidden)]
     __Phantom(std::marker::PhantomData<&'a str>),
 }
 
@@ -115,41 +328,316 @@ impl<'a> Iterator for Entries<'a> {
     type Item = Result<Entry<'a>>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.next_unfiltered()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if self.filters.is_empty() && self.hard_links.is_none() {
+                return Some(Ok(entry));
+            }
+
+            let path = match entry.path() {
+                Ok(path) => normalize(&path),
+                Err(e) => return Some(Err(e)),
+            };
+
+            let accepted =
+                self.filters.iter().all(|filter| filter.accepts(&path));
+
+            let entry = if self.hard_links.is_some() {
+                self.resolve_hard_link(entry, path, accepted)
+            } else {
+                Ok(entry)
+            };
+
+            if accepted {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+impl<'a> Entries<'a> {
+    /// Records where the contents of `entry` at `path` are and returns it,
+    /// with its contents from the spool if it is returned and they have been
+    /// spooled, or with the contents of its target if it is a hard link.
+    fn resolve_hard_link(
+        &mut self,
+        mut entry: Entry<'a>,
+        path: PathBuf,
+        returned: bool,
+    ) -> Result<Entry<'a>> {
+        let seekable = self.inner.is_seekable();
+
+        let Some(links) = &mut self.hard_links else {
+            return Ok(entry);
+        };
+
+        let entry_type = entry.entry_type();
+
+        if entry_type != EntryType::Link {
+            if !entry_type.is_file_or_sparse() {
+                links.contents.insert(path, Contents::Spooled(0, 0));
+                return Ok(entry);
+            }
+
+            if seekable {
+                links
+                    .contents
+                    .insert(path, Contents::Archive(entry.offset()));
+                return Ok(entry);
+            }
+
+            let size = entry.size();
+            let spool = links.spool()?;
+            let offset = spool.push(&mut entry)?;
+            let contents = spool.reader(offset, size)?;
+
+            links.contents.insert(path, Contents::Spooled(offset, size));
+
+            return Ok(Entry::TarSpooled(Box::new(entry), contents, size));
+        }
+
+        let target = normalize(&entry.link_name()?.unwrap_or_default());
+
+        let Some(&contents) = links.contents.get(&target) else {
+            links.contents.remove(&path);
+
+            return if returned {
+                Err(Error::HardLinkTarget(target))
+            } else {
+                Ok(entry)
+            };
+        };
+
+        let (offset, size) = match contents {
+            Contents::Spooled(offset, size) => (offset, size),
+
+            Contents::Archive(_) if !returned => {
+                links.contents.insert(path, contents);
+                return Ok(entry);
+            }
+
+            Contents::Archive(offset) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    target = %target.display(),
+                    offset,
+                    "reading hard link target",
+                );
+
+                let spooled =
+                    links.spool_entry_at(offset, self.paths, self.options)?;
+
+                let contents = Contents::Spooled(spooled.0, spooled.1);
+                links.contents.insert(target, contents);
+
+                spooled
+            }
+        };
+
+        links.contents.insert(path, Contents::Spooled(offset, size));
+
+        if !returned {
+            return Ok(entry);
+        }
+
+        let contents = links.spool()?.reader(offset, size)?;
+
+        Ok(Entry::TarSpooled(Box::new(entry), contents, size))
+    }
+
+    /// Returns `entry`, which was read with its `headers`, with its sparse
+    /// map if it is a sparse file, and moves on to the headers of the next
+    /// entry, which follow its data.
+    #[cfg(feature = "tar")]
+    fn read_headers(
+        &mut self,
+        mut entry: Entry<'a>,
+        headers: &[u8],
+    ) -> Result<Entry<'a>> {
+        let data = self.state.tarball_position();
+        let size = entry.stored_size()?;
+        self.next = data + size.next_multiple_of(BLOCK as u64);
+
+        Ok(match Sparse::of(&mut entry, headers)? {
+            Some(sparse) => Entry::TarSparse(Box::new(entry), sparse),
+            None => entry,
+        })
+    }
+
+    fn next_unfiltered(&mut self) -> Option<Result<Entry<'a>>> {
+        if self.done {
+            return None;
+        }
+
+        if self.state.is_cancelled() {
+            self.done = true;
+            return Some(Err(Error::Cancelled));
+        }
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "next",
+            path = tracing::field::Empty,
+            entry_type = tracing::field::Empty,
+            size = tracing::field::Empty,
+        )
+        .entered();
+
+        self.state.record_from(self.next);
+        let next = self.inner.next_entry(self.next);
+        let headers = self.state.take_recording();
+
+        match next {
+            Some(Ok(entry)) => {
+                #[cfg(feature = "tar")]
+                let entry = match self.read_headers(entry, &headers) {
+                    Ok(entry) => entry,
+                    Err(e) => return Some(Err(e)),
+                };
+
+                self.count += 1;
+
+                #[cfg(feature = "tracing")]
+                {
+                    if let Ok(path) = entry.path() {
+                        span.record("path", path.display().to_string());
+                    }
+
+                    span.record(
+                        "entry_type",
+                        tracing::field::debug(entry.entry_type()),
+                    );
+                    span.record("size", entry.size());
+                }
+
+                if let Some(observer) = &mut self.observer {
+                    let path = entry.path().ok();
+
+                    observer(&Progress {
+                        compressed: self.state.position(),
+                        compressed_total: self.state.len(),
+                        uncompressed: self.inner.base()
+                            + self.state.tarball_position(),
+                        entries: self.count,
+                        path: path.as_deref(),
+                    });
+                }
+
+                Some(Ok(entry))
+            }
+
+            Some(Err(_)) if self.state.is_cancelled() => {
+                self.done = true;
+                Some(Err(Error::Cancelled))
+            }
+
+            #[cfg(feature = "tracing")]
+            Some(Err(e)) => {
+                tracing::warn!(error = %e, "reading entry failed");
+                Some(Err(e))
+            }
+
+            next => next,
+        }
+    }
+}
+
+impl<'a> Inner<'a> {
+    /// Returns the position in the uncompressed archive that the tarball is
+    /// read from, which is not the start for seekable archives that have
+    /// been repositioned.
+    const fn base(&self) -> u64 {
+        match self {
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(_, base) => *base,
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(_, base) => *base,
+
+            #[allow(unreachable_patterns)]
+            _ => 0,
+        }
+    }
+
+    /// Returns true if the archive can be repositioned to an entry, see
+    /// [`Archive::read_entry_at`].
+    const fn is_seekable(&self) -> bool {
+        match self {
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(..) => true,
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(..) => true,
+
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Returns the next entry, whose headers start at `start` of the tarball.
+    fn next_entry(&mut self, start: u64) -> Option<Result<Entry<'a>>> {
         match self {
             #[cfg(feature = "tar")]
             Self::Tar(entries) => entries
                 .next()
-                .map(|r| r.map(Entry::Tar).map_err(From::from)),
+                .map(|r| Entry::from_tar(r, start, Entry::Tar)),
 
             #[cfg(all(feature = "bzip2", feature = "tar"))]
             Self::TarBzip2(entries) => entries
                 .next()
-                .map(|r| r.map(Entry::TarBzip2).map_err(From::from)),
+                .map(|r| Entry::from_tar(r, start, Entry::TarBzip2)),
 
             #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
             Self::TarBzip2Rs(entries) => entries
                 .next()
-                .map(|r| r.map(Entry::TarBzip2Rs).map_err(From::from)),
+                .map(|r| Entry::from_tar(r, start, Entry::TarBzip2Rs)),
 
             #[cfg(all(feature = "flate2", feature = "tar"))]
             Self::TarGzip(entries) => entries
                 .next()
-                .map(|r| r.map(Entry::TarGzip).map_err(From::from)),
+                .map(|r| Entry::from_tar(r, start, Entry::TarGzip)),
 
             #[cfg(all(feature = "lz4", feature = "tar"))]
             Self::TarLz4(entries) => entries
                 .next()
-                .map(|r| r.map(Entry::TarLz4).map_err(From::from)),
+                .map(|r| Entry::from_tar(r, start, Entry::TarLz4)),
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
             Self::TarXz(entries) => entries
                 .next()
-                .map(|r| r.map(Entry::TarXz).map_err(From::from)),
+                .map(|r| Entry::from_tar(r, start, Entry::TarXz)),
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(entries, base) => entries.next().map(|r| {
+                Entry::from_tar(r, *base + start, Entry::TarXzSeekable)
+            }),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
             Self::TarZstd(entries) => entries
                 .next()
-                .map(|r| r.map(Entry::TarZstd).map_err(From::from)),
+                .map(|r| Entry::from_tar(r, start, Entry::TarZstd)),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(entries, base) => entries.next().map(|r| {
+                Entry::from_tar(r, *base + start, Entry::TarZstdSeekable)
+            }),
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entries) => entries
+                .next()
+                .map(|r| Entry::from_tar(r, start, Entry::TarParallel)),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entries) => entries
+                .next()
+                .map(|r| Entry::from_tar(r, start, Entry::TarReadAhead)),
         }
     }
 }
@@ -1,17 +1,17 @@
 use std::borrow::Cow;
-#[cfg(feature = "tar")]
-use std::fs::File;
+use std::fs::{self, File};
 #[cfg(feature = "zstd")]
 use std::io::BufReader;
-use std::io::Read;
-use std::path::Path;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 
 #[cfg(feature = "bzip2")]
 use bzip2::read::BzDecoder as BzSysDecoder;
 #[cfg(feature = "bzip2-rs")]
 use bzip2_rs::decoder::DecoderReader as BzNativeDecoder;
 #[cfg(feature = "flate2")]
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 #[cfg(feature = "lz4")]
 use lz4::Decoder as Lz4Decoder;
 #[cfg(feature = "xz2")]
@@ -19,6 +19,20 @@ use xz2::read::XzDecoder;
 #[cfg(feature = "zstd")]
 use zstd::stream::read::Decoder as ZstdDecoder;
 
+#[cfg(all(
+    feature = "tar",
+    any(feature = "flate2", feature = "xz2", feature = "zstd")
+))]
+use crate::parallel::Parallel;
+#[cfg(feature = "tar")]
+use crate::read_ahead::ReadAhead;
+#[cfg(all(feature = "tar", any(feature = "xz2", feature = "zstd")))]
+use crate::seekable::Seekable;
+use crate::source::{Source, Tracked};
+#[cfg(feature = "tar")]
+use crate::sparse::{Sparse, SparseFormat, SparseMap};
+#[cfg(feature = "tar")]
+use crate::spool::SpoolReader;
 use crate::Result;
 
 /// Archive entry.
@@ -27,61 +41,190 @@ use crate::Result;
 pub enum Entry<'a> {
     #[cfg(feature = "tar")]
     #[doc(hidden)]
-    Tar(tar::Entry<'a, File>),
+    Tar(tar::Entry<'a, Tracked<Source>>, Info),
 
     #[cfg(all(feature = "bzip2", feature = "tar"))]
     #[doc(hidden)]
-    TarBzip2(tar::Entry<'a, BzSysDecoder<File>>),
+    TarBzip2(tar::Entry<'a, Tracked<BzSysDecoder<Source>>>, Info),
 
     #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
     #[doc(hidden)]
-    TarBzip2Rs(tar::Entry<'a, BzNativeDecoder<File>>),
+    TarBzip2Rs(tar::Entry<'a, Tracked<BzNativeDecoder<Source>>>, Info),
 
     #[cfg(all(feature = "flate2", feature = "tar"))]
     #[doc(hidden)]
-    TarGzip(tar::Entry<'a, GzDecoder<File>>),
+    TarGzip(tar::Entry<'a, Tracked<MultiGzDecoder<Source>>>, Info),
 
     #[cfg(all(feature = "lz4", feature = "tar"))]
     #[doc(hidden)]
-    TarLz4(tar::Entry<'a, Lz4Decoder<File>>),
+    TarLz4(tar::Entry<'a, Tracked<Lz4Decoder<Source>>>, Info),
+
+    #[cfg(all(feature = "xz2", feature = "tar"))]
+    #[doc(hidden)]
+    TarXz(tar::Entry<'a, Tracked<XzDecoder<Source>>>, Info),
 
     #[cfg(all(feature = "xz2", feature = "tar"))]
     #[doc(hidden)]
-    TarXz(tar::Entry<'a, XzDecoder<File>>),
+    TarXzSeekable(tar::Entry<'a, Tracked<Seekable>>, Info),
+
+    #[cfg(all(feature = "zstd", feature = "tar"))]
+    #[doc(hidden)]
+    TarZstd(
+        tar::Entry<'a, Tracked<ZstdDecoder<'static, BufReader<Source>>>>,
+        Info,
+    ),
 
     #[cfg(all(feature = "zstd", feature = "tar"))]
     #[doc(hidden)]
-    TarZstd(tar::Entry<'a, ZstdDecoder<'static, BufReader<File>>>),
+    TarZstdSeekable(tar::Entry<'a, Tracked<Seekable>>, Info),
+
+    #[cfg(all(
+        feature = "tar",
+        any(feature = "flate2", feature = "xz2", feature = "zstd")
+    ))]
+    #[doc(hidden)]
+    TarParallel(tar::Entry<'a, Tracked<Parallel>>, Info),
+
+    #[cfg(feature = "tar")]
+    #[doc(hidden)]
+    TarReadAhead(tar::Entry<'a, Tracked<ReadAhead>>, Info),
+
+    #[cfg(feature = "tar")]
+    #[doc(hidden)]
+    TarNested(tar::Entry<'a, Tracked<&'a mut (dyn Read + 'a)>>, Info),
+
+    #[cfg(feature = "tar")]
+    #[doc(hidden)]
+    TarSpooled(Box<Self>, SpoolReader, u64),
+
+    #[cfg(feature = "tar")]
+    #[doc(hidden)]
+    TarSparse(Box<Self>, Sparse),
 
     #[cfg(not(feature = "tar"))]
     #[doc(hidden)]
     __Phantom(std::marker::PhantomData<&'a str>),
 }
 
+/// Offset and metadata of the PAX extended header of a tar entry, read once
+/// when the entry is created.
+#[cfg(feature = "tar")]
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Info {
+    /// Offset of the first header of the entry in the uncompressed archive.
+    offset: u64,
+
+    // PAX records that override header fields
+    size: Option<u64>,
+    uid: Option<u64>,
+    gid: Option<u64>,
+    mtime: Option<u64>,
+}
+
+#[cfg(feature = "tar")]
+impl Info {
+    /// Returns the info of `entry`, whose headers start at `offset`.
+    fn read<R: Read>(
+        entry: &mut tar::Entry<'_, R>,
+        offset: u64,
+    ) -> io::Result<Self> {
+        let mut info = Self {
+            offset,
+            ..Self::default()
+        };
+
+        let Some(extensions) = entry.pax_extensions()? else {
+            return Ok(info);
+        };
+
+        for extension in extensions {
+            let extension = extension?;
+
+            let field = match extension.key() {
+                Ok("size") => &mut info.size,
+                Ok("uid") => &mut info.uid,
+                Ok("gid") => &mut info.gid,
+                Ok("mtime") => &mut info.mtime,
+                _ => continue,
+            };
+
+            // the last record counts
+            *field = pax_number(extension.value_bytes());
+        }
+
+        Ok(info)
+    }
+}
+
+#[cfg(feature = "tar")]
 impl<'a> Entry<'a> {
+    /// Returns the tar `entry` as `variant`, with its headers at `offset` of
+    /// the uncompressed archive.
+    pub(crate) fn from_tar<R: Read>(
+        entry: io::Result<tar::Entry<'a, R>>,
+        offset: u64,
+        variant: impl FnOnce(tar::Entry<'a, R>, Info) -> Self,
+    ) -> Result<Self> {
+        let mut entry = entry?;
+        let info = Info::read(&mut entry, offset)?;
+
+        Ok(variant(entry, info))
+    }
+}
+
+impl Entry<'_> {
     /// Returns the file type of this entry.
     pub fn entry_type(&self) -> EntryType {
         match self {
             #[cfg(feature = "tar")]
-            Self::Tar(entry) => entry.header().entry_type().into(),
+            Self::Tar(entry, _) => entry.header().entry_type().into(),
 
             #[cfg(all(feature = "bzip2", feature = "tar"))]
-            Self::TarBzip2(entry) => entry.header().entry_type().into(),
+            Self::TarBzip2(entry, _) => entry.header().entry_type().into(),
 
             #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-            Self::TarBzip2Rs(entry) => entry.header().entry_type().into(),
+            Self::TarBzip2Rs(entry, _) => entry.header().entry_type().into(),
 
             #[cfg(all(feature = "flate2", feature = "tar"))]
-            Self::TarGzip(entry) => entry.header().entry_type().into(),
+            Self::TarGzip(entry, _) => entry.header().entry_type().into(),
 
             #[cfg(all(feature = "lz4", feature = "tar"))]
-            Self::TarLz4(entry) => entry.header().entry_type().into(),
+            Self::TarLz4(entry, _) => entry.header().entry_type().into(),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(entry, _) => entry.header().entry_type().into(),
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
-            Self::TarXz(entry) => entry.header().entry_type().into(),
+            Self::TarXzSeekable(entry, _) => {
+                entry.header().entry_type().into()
+            }
 
             #[cfg(all(feature = "zstd", feature = "tar"))]
-            Self::TarZstd(entry) => entry.header().entry_type().into(),
+            Self::TarZstd(entry, _) => entry.header().entry_type().into(),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(entry, _) => {
+                entry.header().entry_type().into()
+            }
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => entry.header().entry_type().into(),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => entry.header().entry_type().into(),
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.header().entry_type().into(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(entry, _, _) => entry.entry_type(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, _) => entry.entry_type(),
         }
     }
 
@@ -90,55 +233,546 @@ impl<'a> Entry<'a> {
     pub fn size(&self) -> u64 {
         match self {
             #[cfg(feature = "tar")]
-            Self::Tar(entry) => entry.size(),
+            Self::Tar(entry, _) => entry.size(),
 
             #[cfg(all(feature = "bzip2", feature = "tar"))]
-            Self::TarBzip2(entry) => entry.size(),
+            Self::TarBzip2(entry, _) => entry.size(),
 
             #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-            Self::TarBzip2Rs(entry) => entry.size(),
+            Self::TarBzip2Rs(entry, _) => entry.size(),
 
             #[cfg(all(feature = "flate2", feature = "tar"))]
-            Self::TarGzip(entry) => entry.size(),
+            Self::TarGzip(entry, _) => entry.size(),
 
             #[cfg(all(feature = "lz4", feature = "tar"))]
-            Self::TarLz4(entry) => entry.size(),
+            Self::TarLz4(entry, _) => entry.size(),
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
-            Self::TarXz(entry) => entry.size(),
+            Self::TarXz(entry, _) => entry.size(),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(entry, _) => entry.size(),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(entry, _) => entry.size(),
 
             #[cfg(all(feature = "zstd", feature = "tar"))]
-            Self::TarZstd(entry) => entry.size(),
+            Self::TarZstdSeekable(entry, _) => entry.size(),
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => entry.size(),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => entry.size(),
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.size(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(_, _, size) => *size,
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(_, sparse) => sparse.map().apparent_size,
         }
     }
 
+    /// Returns the offset of this entry's headers in the uncompressed
+    /// archive, i.e. of the first of its PAX or GNU extension headers that
+    /// precede its header, if any.
+    ///
+    /// The offset can be used to read this entry again with
+    /// [`Archive::read_entry_at`](crate::Archive::read_entry_at).
+    #[must_use]
+    pub const fn offset(&self) -> u64 {
+        self.info().offset
+    }
+
     /// Returns the path name for this entry.
     ///
     /// # Errors
     ///
     /// Parsing the path metadata.
-    pub fn path(&self) -> Result<Cow<Path>> {
+    pub fn path(&self) -> Result<Cow<'_, Path>> {
+        match self {
+            #[cfg(feature = "tar")]
+            Self::Tar(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "bzip2", feature = "tar"))]
+            Self::TarBzip2(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
+            Self::TarBzip2Rs(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "flate2", feature = "tar"))]
+            Self::TarGzip(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "lz4", feature = "tar"))]
+            Self::TarLz4(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(entry, _) => {
+                entry.path().map_err(From::from)
+            }
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.path().map_err(From::from),
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(entry, _, _) => entry.path(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, sparse) => sparse
+                .name()
+                .map_or_else(|| entry.path(), |name| Ok(Cow::Borrowed(name))),
+        }
+    }
+
+    /// Returns the permission bits of this entry, like `0o644`.
+    ///
+    /// # Errors
+    ///
+    /// Parsing the header field.
+    pub fn mode(&self) -> Result<u32> {
+        Ok(self.header().mode()?)
+    }
+
+    /// Returns the numeric user ID of the owner of this entry, from its PAX
+    /// `uid` record if it has one.
+    ///
+    /// # Errors
+    ///
+    /// Parsing the header field.
+    pub fn uid(&self) -> Result<u64> {
+        match self.info().uid {
+            Some(uid) => Ok(uid),
+            None => Ok(self.header().uid()?),
+        }
+    }
+
+    /// Returns the numeric group ID of the owner of this entry, from its PAX
+    /// `gid` record if it has one.
+    ///
+    /// # Errors
+    ///
+    /// Parsing the header field.
+    pub fn gid(&self) -> Result<u64> {
+        match self.info().gid {
+            Some(gid) => Ok(gid),
+            None => Ok(self.header().gid()?),
+        }
+    }
+
+    /// Returns the modification time of this entry in seconds since the Unix
+    /// epoch, from its PAX `mtime` record without the fraction if it has one.
+    ///
+    /// # Errors
+    ///
+    /// Parsing the header field.
+    pub fn mtime(&self) -> Result<u64> {
+        match self.info().mtime {
+            Some(mtime) => Ok(mtime),
+            None => Ok(self.header().mtime()?),
+        }
+    }
+
+    /// Returns the target of this entry if it is a hard or symbolic link.
+    ///
+    /// # Errors
+    ///
+    /// Parsing the link metadata.
+    pub fn link_name(&self) -> Result<Option<Cow<'_, Path>>> {
+        match self {
+            #[cfg(feature = "tar")]
+            Self::Tar(entry, _) => entry.link_name().map_err(From::from),
+
+            #[cfg(all(feature = "bzip2", feature = "tar"))]
+            Self::TarBzip2(entry, _) => entry.link_name().map_err(From::from),
+
+            #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
+            Self::TarBzip2Rs(entry, _) => {
+                entry.link_name().map_err(From::from)
+            }
+
+            #[cfg(all(feature = "flate2", feature = "tar"))]
+            Self::TarGzip(entry, _) => entry.link_name().map_err(From::from),
+
+            #[cfg(all(feature = "lz4", feature = "tar"))]
+            Self::TarLz4(entry, _) => entry.link_name().map_err(From::from),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(entry, _) => entry.link_name().map_err(From::from),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(entry, _) => {
+                entry.link_name().map_err(From::from)
+            }
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(entry, _) => entry.link_name().map_err(From::from),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(entry, _) => {
+                entry.link_name().map_err(From::from)
+            }
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => {
+                entry.link_name().map_err(From::from)
+            }
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => {
+                entry.link_name().map_err(From::from)
+            }
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.link_name().map_err(From::from),
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(entry, _, _) => entry.link_name(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, _) => entry.link_name(),
+        }
+    }
+
+    /// Returns the map of the data regions if this entry is a sparse file.
+    ///
+    /// The [`size`](Self::size) of sparse files is their apparent size, see
+    /// [`SparseMap::stored_size`] for the size of the data in the archive.
+    /// Holes read as zeros, and [`unpack_in`](Self::unpack_in) seeks over
+    /// them, so that they are holes in the extracted file on file systems
+    /// that support them.
+    #[cfg(feature = "tar")]
+    #[must_use]
+    pub fn sparse_map(&self) -> Option<&SparseMap> {
+        match self {
+            Self::TarSparse(_, sparse) => Some(sparse.map()),
+            Self::TarSpooled(entry, _, _) => entry.sparse_map(),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "tar")]
+    pub(crate) fn header(&self) -> &tar::Header {
+        match self {
+            #[cfg(feature = "tar")]
+            Self::Tar(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "bzip2", feature = "tar"))]
+            Self::TarBzip2(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
+            Self::TarBzip2Rs(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "flate2", feature = "tar"))]
+            Self::TarGzip(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "lz4", feature = "tar"))]
+            Self::TarLz4(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(entry, _) => entry.header(),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(entry, _) => entry.header(),
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => entry.header(),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => entry.header(),
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.header(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(entry, _, _) => entry.header(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, _) => entry.header(),
+        }
+    }
+
+    /// Returns the size of the data of this entry in the tarball, without
+    /// padding, which excludes the holes of old GNU sparse files.
+    #[cfg(feature = "tar")]
+    pub(crate) fn stored_size(&self) -> Result<u64> {
+        if self.entry_type() != EntryType::GNUSparse {
+            return Ok(self.size());
+        }
+
+        match self.info().size {
+            Some(size) => Ok(size),
+            None => Ok(self.header().entry_size()?),
+        }
+    }
+
+    /// Returns the metadata that is read when this entry is created.
+    #[cfg(feature = "tar")]
+    const fn info(&self) -> &Info {
         match self {
             #[cfg(feature = "tar")]
-            Self::Tar(entry) => entry.path().map_err(From::from),
+            Self::Tar(_, info) => info,
+
+            #[cfg(all(feature = "bzip2", feature = "tar"))]
+            Self::TarBzip2(_, info) => info,
+
+            #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
+            Self::TarBzip2Rs(_, info) => info,
+
+            #[cfg(all(feature = "flate2", feature = "tar"))]
+            Self::TarGzip(_, info) => info,
+
+            #[cfg(all(feature = "lz4", feature = "tar"))]
+            Self::TarLz4(_, info) => info,
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(_, info) => info,
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(_, info) => info,
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(_, info) => info,
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(_, info) => info,
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(_, info) => info,
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(_, info) => info,
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(_, info) => info,
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(entry, _, _) => entry.info(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, _) => entry.info(),
+        }
+    }
+
+    /// Returns the records of the PAX extended header of this entry, as keys
+    /// and raw values in the order they are stored, or none if there is no
+    /// such header.
+    ///
+    /// Records whose keys are not UTF-8 are skipped, see
+    /// [`Entry::attributes`] for decoded extended attributes and ACLs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the extended header fails.
+    #[cfg(feature = "tar")]
+    pub fn pax_extensions(&mut self) -> Result<Vec<(String, Vec<u8>)>> {
+        let extensions = match self {
+            #[cfg(feature = "tar")]
+            Self::Tar(entry, _) => entry.pax_extensions()?,
 
             #[cfg(all(feature = "bzip2", feature = "tar"))]
-            Self::TarBzip2(entry) => entry.path().map_err(From::from),
+            Self::TarBzip2(entry, _) => entry.pax_extensions()?,
 
             #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-            Self::TarBzip2Rs(entry) => entry.path().map_err(From::from),
+            Self::TarBzip2Rs(entry, _) => entry.pax_extensions()?,
 
             #[cfg(all(feature = "flate2", feature = "tar"))]
-            Self::TarGzip(entry) => entry.path().map_err(From::from),
+            Self::TarGzip(entry, _) => entry.pax_extensions()?,
 
             #[cfg(all(feature = "lz4", feature = "tar"))]
-            Self::TarLz4(entry) => entry.path().map_err(From::from),
+            Self::TarLz4(entry, _) => entry.pax_extensions()?,
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(entry, _) => entry.pax_extensions()?,
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
-            Self::TarXz(entry) => entry.path().map_err(From::from),
+            Self::TarXzSeekable(entry, _) => entry.pax_extensions()?,
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(entry, _) => entry.pax_extensions()?,
 
             #[cfg(all(feature = "zstd", feature = "tar"))]
-            Self::TarZstd(entry) => entry.path().map_err(From::from),
+            Self::TarZstdSeekable(entry, _) => entry.pax_extensions()?,
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => entry.pax_extensions()?,
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => entry.pax_extensions()?,
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.pax_extensions()?,
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(entry, _, _) => return entry.pax_extensions(),
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, _) => return entry.pax_extensions(),
+        };
+
+        let Some(extensions) = extensions else {
+            return Ok(vec![]);
+        };
+
+        let mut records = vec![];
+
+        for extension in extensions {
+            let extension = extension?;
+
+            if let Ok(key) = extension.key() {
+                records
+                    .push((key.to_owned(), extension.value_bytes().to_vec()));
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Extracts this entry into the directory `dst`.
+    ///
+    /// Returns `false` if the entry was skipped because its path would be
+    /// outside of `dst`, e.g. because it contains `..`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if creating the file or reading the entry fails.
+    pub fn unpack_in(&mut self, dst: impl AsRef<Path>) -> Result<bool> {
+        let dst = dst.as_ref();
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "unpack",
+            path = tracing::field::Empty,
+            offset = self.offset(),
+            size = self.size(),
+        )
+        .entered();
+
+        #[cfg(feature = "tracing")]
+        if let Ok(path) = self.path() {
+            span.record("path", path.display().to_string());
+        }
+
+        let unpacked = self.unpack_in_inner(dst)?;
+
+        #[cfg(feature = "tracing")]
+        if !unpacked {
+            tracing::warn!(
+                dst = %dst.display(),
+                "skipped entry outside of destination",
+            );
+        }
+
+        Ok(unpacked)
+    }
+
+    fn unpack_in_inner(&mut self, dst: &Path) -> std::io::Result<bool> {
+        match self {
+            #[cfg(feature = "tar")]
+            Self::Tar(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "bzip2", feature = "tar"))]
+            Self::TarBzip2(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
+            Self::TarBzip2Rs(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "flate2", feature = "tar"))]
+            Self::TarGzip(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "lz4", feature = "tar"))]
+            Self::TarLz4(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.unpack_in(dst),
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(entry, contents, _) => {
+                // hard links are extracted as links to their target
+                if entry.entry_type() == EntryType::Link {
+                    return entry.unpack_in_inner(dst);
+                }
+
+                let name =
+                    entry.path().map_err(io::Error::other)?.into_owned();
+                let header = entry.header().clone();
+
+                unpack_file(dst, &name, &header, |file| {
+                    io::copy(contents, file).map(drop)
+                })
+            }
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, sparse) => {
+                // tar seeks over the holes of old GNU sparse files itself
+                if sparse.map().format == SparseFormat::OldGnu {
+                    entry.unpack_in_inner(dst)
+                } else {
+                    sparse.unpack_in(entry, dst)
+                }
+            }
         }
     }
 }
@@ -150,29 +784,140 @@ impl Read for Entry<'_> {
     ) -> std::result::Result<usize, std::io::Error> {
         match self {
             #[cfg(feature = "tar")]
-            Self::Tar(entry) => entry.read(buf),
+            Self::Tar(entry, _) => entry.read(buf),
 
             #[cfg(all(feature = "bzip2", feature = "tar"))]
-            Self::TarBzip2(entry) => entry.read(buf),
+            Self::TarBzip2(entry, _) => entry.read(buf),
 
             #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
-            Self::TarBzip2Rs(entry) => entry.read(buf),
+            Self::TarBzip2Rs(entry, _) => entry.read(buf),
 
             #[cfg(all(feature = "flate2", feature = "tar"))]
-            Self::TarGzip(entry) => entry.read(buf),
+            Self::TarGzip(entry, _) => entry.read(buf),
 
             #[cfg(all(feature = "lz4", feature = "tar"))]
-            Self::TarLz4(entry) => entry.read(buf),
+            Self::TarLz4(entry, _) => entry.read(buf),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(entry, _) => entry.read(buf),
 
             #[cfg(all(feature = "xz2", feature = "tar"))]
-            Self::TarXz(entry) => entry.read(buf),
+            Self::TarXzSeekable(entry, _) => entry.read(buf),
 
             #[cfg(all(feature = "zstd", feature = "tar"))]
-            Self::TarZstd(entry) => entry.read(buf),
+            Self::TarZstd(entry, _) => entry.read(buf),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(entry, _) => entry.read(buf),
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(entry, _) => entry.read(buf),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(entry, _) => entry.read(buf),
+
+            #[cfg(feature = "tar")]
+            Self::TarNested(entry, _) => entry.read(buf),
+
+            #[cfg(feature = "tar")]
+            Self::TarSpooled(_, contents, _) => contents.read(buf),
+
+            #[cfg(feature = "tar")]
+            Self::TarSparse(entry, sparse) => sparse.read(entry, buf),
         }
     }
 }
 
+/// Returns the value of a PAX record as a number, without the fraction of
+/// times, or `None` if it is not a non-negative number.
+#[cfg(feature = "tar")]
+fn pax_number(value: &[u8]) -> Option<u64> {
+    let value = std::str::from_utf8(value).ok()?;
+    value.split('.').next().unwrap_or_default().parse().ok()
+}
+
+/// Returns the path that the entry at `path` is extracted to in `dst`, like
+/// `tar`, or `None` if it contains `..`.
+pub fn unpack_path(dst: &Path, path: &Path) -> Option<PathBuf> {
+    let mut unpacked = dst.to_owned();
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(..) | Component::RootDir | Component::CurDir => {
+            }
+            Component::ParentDir => return None,
+            Component::Normal(part) => unpacked.push(part),
+        }
+    }
+
+    Some(unpacked)
+}
+
+/// Extracts the file of `header` at `path` into the directory `dst` with the
+/// contents that `write` writes, or returns `false` if it would be outside of
+/// `dst`.
+///
+/// Like `tar::Entry::unpack_in`, paths with `..` and paths that lead outside
+/// of `dst` through symbolic links are skipped, and the permissions and
+/// modification time are restored.
+#[cfg(feature = "tar")]
+pub fn unpack_file(
+    dst: &Path,
+    path: &Path,
+    header: &tar::Header,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> io::Result<bool> {
+    let Some(file_dst) = unpack_path(dst, path) else {
+        return Ok(false);
+    };
+
+    if file_dst == dst {
+        return Ok(true);
+    }
+
+    if let Some(parent) = file_dst.parent() {
+        fs::create_dir_all(parent)?;
+
+        if !fs::canonicalize(parent)?.starts_with(fs::canonicalize(dst)?) {
+            return Ok(false);
+        }
+    }
+
+    match fs::remove_file(&file_dst) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    let mut file = File::options()
+        .write(true)
+        .create_new(true)
+        .open(&file_dst)?;
+
+    write(&mut file)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = header.mode()? & 0o777;
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(header.mtime()?))?;
+
+    Ok(true)
+}
+
+/// Returns `path` without `.` components and trailing slashes.
+pub fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
 /// File type of an entry.
 // ALLOW this is not a public module
 #[allow(clippy::module_name_repetitions)]
@@ -230,6 +975,22 @@ impl EntryType {
     pub fn is_file(&self) -> bool {
         self == &Self::Regular
     }
+
+    /// Returns true if this is a regular file, including old GNU sparse
+    /// files, whose contents are read with their holes.
+    pub(crate) fn is_file_or_sparse(self) -> bool {
+        self.is_file() || self == Self::GNUSparse
+    }
+
+    /// Returns the type of the extracted entry, a regular file for old GNU
+    /// sparse files.
+    pub(crate) fn extracted(self) -> Self {
+        if self == Self::GNUSparse {
+            Self::Regular
+        } else {
+            self
+        }
+    }
 }
 
 #[cfg(feature = "tar")]
@@ -253,3 +1014,48 @@ impl From<tar::EntryType> for EntryType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Returns the owner and modification time of `entry`, borrowed like in
+    /// a listing.
+    fn owner_and_mtime(entry: &Entry<'_>) -> (u64, u64, u64) {
+        let uid = entry.uid().unwrap();
+        let gid = entry.gid().unwrap();
+        (uid, gid, entry.mtime().unwrap())
+    }
+
+    #[test]
+    fn pax_records_override_header_fields() {
+        let tarball = testing::tarball(|builder| {
+            let records: [(&str, &[u8]); 3] = [
+                ("uid", b"3000000000"),
+                ("gid", b"42"),
+                ("mtime", b"1700000001.5"),
+            ];
+
+            builder.append_pax_extensions(records).unwrap();
+            testing::file(builder, "pax", b"pax");
+            testing::file(builder, "plain", b"plain");
+        });
+
+        let (_file, mut archive) = testing::open(&tarball, ".tar");
+
+        let read: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| owner_and_mtime(&entry.unwrap()))
+            .collect();
+
+        assert_eq!(
+            read,
+            [
+                (3_000_000_000, 42, 1_700_000_001),
+                (1000, 1000, 1_700_000_000)
+            ]
+        );
+    }
+}
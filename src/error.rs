@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use crate::support::Format;
+
 /// Archive error.
 #[derive(thiserror::Error, Debug)]
 // NONEXHAUSTIVE new formats could add new error types
@@ -12,6 +14,80 @@ pub enum Error {
     /// Unsupported archive file type.
     #[error("unsupported archive file type: {0}")]
     UnsupportedArchiveType(PathBuf),
+
+    /// Archive format that cannot be written.
+    #[error("writing is not supported for {0}")]
+    UnsupportedWriteFormat(Format),
+
+    /// Archive format that cannot be appended to.
+    #[error("appending is not supported for {0}")]
+    UnsupportedAppendFormat(Format),
+
+    /// Volume of a split archive, which cannot be appended to.
+    #[error("appending is not supported for volumes: {0}")]
+    UnsupportedAppendVolume(PathBuf),
+
+    /// Processing the archive has been cancelled.
+    #[error("archive processing was cancelled")]
+    Cancelled,
+
+    /// No entry header at offset.
+    #[error("no entry header at offset {0}")]
+    InvalidOffset(u64),
+
+    /// No entry with path.
+    #[error("no entry at {0}")]
+    EntryNotFound(PathBuf),
+
+    /// Hard link whose target is not an earlier entry.
+    #[error("hard link target is not an earlier entry: {0}")]
+    HardLinkTarget(PathBuf),
+
+    /// Malformed map of a sparse file.
+    #[error("invalid sparse map: {0}")]
+    InvalidSparseMap(String),
+
+    /// Malformed POSIX.1e ACL entry.
+    #[error("invalid ACL entry: {0}")]
+    InvalidAcl(String),
+
+    /// Entry that is not a directory.
+    #[error("not a directory: {0}")]
+    NotADirectory(PathBuf),
+
+    /// Entry that is not a regular file.
+    #[error("not a regular file: {0}")]
+    NotAFile(PathBuf),
+
+    /// Entry that is not a symbolic link.
+    #[error("not a symbolic link: {0}")]
+    NotASymlink(PathBuf),
+
+    /// Path with symbolic links that are nested too deeply or form a loop.
+    #[error("too many levels of symbolic links: {0}")]
+    SymlinkLoop(PathBuf),
+
+    /// Entries of merged archives with the same path.
+    #[error("conflicting entries at {0}")]
+    Conflict(PathBuf),
+
+    /// Invalid wildcard pattern.
+    #[cfg(feature = "glob")]
+    #[error("invalid pattern: {0}")]
+    Glob(#[from] globset::Error),
+
+    /// Invalid regular expression.
+    #[cfg(feature = "regex")]
+    #[error("invalid regular expression: {0}")]
+    Regex(#[from] regex::Error),
+
+    /// Malformed checksum manifest.
+    #[error("invalid manifest: {0}")]
+    InvalidManifest(String),
+
+    /// `SOURCE_DATE_EPOCH` is not a number of seconds.
+    #[error("invalid SOURCE_DATE_EPOCH: {0}")]
+    InvalidSourceDateEpoch(String),
 }
 
 // used internally
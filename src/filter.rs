@@ -0,0 +1,165 @@
+use std::path::Path;
+
+#[cfg(feature = "glob")]
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+#[cfg(feature = "glob")]
+use crate::Result;
+
+/// Filter of the entries, see [`Entries`](crate::Entries).
+#[derive(Clone, Debug)]
+// ALLOW there are no filters without the glob and regex features
+#[allow(clippy::empty_enums)]
+pub enum Filter {
+    #[cfg(feature = "glob")]
+    Matching(GlobSet),
+
+    #[cfg(feature = "glob")]
+    Excluding(GlobSet),
+
+    #[cfg(feature = "regex")]
+    MatchingRegex(Regex),
+
+    #[cfg(feature = "regex")]
+    ExcludingRegex(Regex),
+}
+
+impl Filter {
+    /// Returns true if the entry at `path` passes this filter.
+    // ALLOW there are no filters without the glob and regex features
+    #[allow(unused_variables, clippy::missing_const_for_fn)]
+    pub fn accepts(&self, path: &Path) -> bool {
+        match self {
+            #[cfg(feature = "glob")]
+            Self::Matching(globs) => globs.is_match(path),
+
+            #[cfg(feature = "glob")]
+            Self::Excluding(globs) => !globs.is_match(path),
+
+            #[cfg(feature = "regex")]
+            Self::MatchingRegex(regex) => {
+                regex.is_match(&path.to_string_lossy())
+            }
+
+            #[cfg(feature = "regex")]
+            Self::ExcludingRegex(regex) => {
+                !regex.is_match(&path.to_string_lossy())
+            }
+
+            #[allow(unreachable_patterns)]
+            _ => true,
+        }
+    }
+}
+
+/// Builder of a set of wildcard patterns with the semantics of `tar
+/// --wildcards`.
+///
+/// Like in `tar`, `*` and `?` also match `/`, and a pattern that matches a
+/// directory also matches everything below it. Leading `./` and trailing `/`
+/// of entry paths are ignored.
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::{Archive, Wildcards};
+///
+/// let include = Wildcards::new().add("src/**/*.rs").build()?;
+/// let exclude = Wildcards::new().anchored(false).add("target").build()?;
+///
+/// let mut archive = Archive::open("crate.tar.gz")?;
+///
+/// for entry in archive.entries()?.matching(include).excluding(exclude) {
+///     println!("{}", entry?.path()?.display());
+/// }
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+#[cfg(feature = "glob")]
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct Wildcards {
+    patterns: Vec<String>,
+    anchored: bool,
+    case_insensitive: bool,
+}
+
+#[cfg(feature = "glob")]
+impl Wildcards {
+    /// Returns an empty set of anchored, case-sensitive patterns.
+    pub const fn new() -> Self {
+        Self {
+            patterns: vec![],
+            anchored: true,
+            case_insensitive: false,
+        }
+    }
+
+    /// Sets whether patterns have to match from the start of the path,
+    /// enabled by default, like `tar --anchored`.
+    ///
+    /// Unanchored patterns match after any `/`, which is the default of `tar
+    /// --exclude`.
+    pub const fn anchored(&mut self, anchored: bool) -> &mut Self {
+        self.anchored = anchored;
+        self
+    }
+
+    /// Sets whether patterns ignore case, disabled by default, like `tar
+    /// --ignore-case`.
+    pub const fn case_insensitive(
+        &mut self,
+        case_insensitive: bool,
+    ) -> &mut Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Adds a pattern.
+    pub fn add(&mut self, pattern: impl Into<String>) -> &mut Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    /// Returns the compiled set of patterns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern is invalid.
+    pub fn build(&self) -> Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+
+        for pattern in &self.patterns {
+            let pattern =
+                pattern.trim_start_matches("./").trim_end_matches('/');
+
+            let mut patterns = vec![pattern.to_owned()];
+
+            if !self.anchored {
+                patterns.push(format!("**/{pattern}"));
+            }
+
+            for pattern in patterns {
+                for pattern in [pattern.clone(), format!("{pattern}/**")] {
+                    let glob = GlobBuilder::new(&pattern)
+                        .literal_separator(false)
+                        .backslash_escape(true)
+                        .case_insensitive(self.case_insensitive)
+                        .build()?;
+
+                    builder.add(glob);
+                }
+            }
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(feature = "glob")]
+impl Default for Wildcards {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,208 @@
+//! Integrity test of whole archives, like `gzip -t` or `xz -t`.
+
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::archive::Inner;
+use crate::{Archive, Error, Result};
+
+/// Result of testing the integrity of an archive, see [`Archive::test`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct TestReport {
+    /// Number of entries that have been decoded.
+    pub entries: u64,
+
+    /// Number of bytes of entry contents that have been decoded.
+    pub bytes: u64,
+
+    /// Failures in the order they have been encountered.
+    pub failures: Vec<Failure>,
+}
+
+impl TestReport {
+    /// Returns true if the archive has been decoded without failures.
+    #[must_use]
+    pub const fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Integrity failure of an archive.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Failure {
+    /// Path of the entry that failed, if any.
+    pub path: Option<PathBuf>,
+
+    /// Offset of the header of the entry that failed in the uncompressed
+    /// archive, if any.
+    pub offset: Option<u64>,
+
+    /// Error decoding the archive.
+    pub error: Error,
+}
+
+impl Archive {
+    /// Tests the integrity of this archive by decoding every entry to
+    /// completion, stopping at the first failure.
+    ///
+    /// This verifies the tar header checksums, entry sizes and all checks
+    /// of the compression format: gzip CRC-32 and size, bzip2 block CRCs, xz
+    /// block checks, zstd content checksums and lz4 frame checksums, if the
+    /// archive has been written with them. Data following the end of the
+    /// tarball is decoded, too, so the checks at the end of the compressed
+    /// stream are verified.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use archive_rs::Archive;
+    ///
+    /// let report = Archive::open("backup.tar.zst")?.test()?;
+    ///
+    /// for failure in &report.failures {
+    ///     eprintln!("{}", failure.error);
+    /// }
+    /// # Ok::<(), archive_rs::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if processing the archive has been cancelled.
+    /// Failures while decoding are reported in the [`TestReport`].
+    pub fn test(self) -> Result<TestReport> {
+        self.test_with(false)
+    }
+
+    /// Tests the integrity of this archive like [`Archive::test`], but
+    /// continues after entries that fail to decode, as far as the archive
+    /// can be read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if processing the archive has been cancelled.
+    /// Failures while decoding are reported in the [`TestReport`].
+    pub fn test_all(self) -> Result<TestReport> {
+        self.test_with(true)
+    }
+
+    fn test_with(mut self, all: bool) -> Result<TestReport> {
+        let mut report = TestReport::default();
+        let state = Arc::clone(&self.state);
+
+        for entry in self.entries()? {
+            let mut entry = match entry {
+                Ok(entry) => entry,
+                Err(Error::Cancelled) => return Err(Error::Cancelled),
+                Err(error) => {
+                    // iterating cannot continue after a broken header
+                    report.failures.push(Failure {
+                        path: None,
+                        offset: None,
+                        error,
+                    });
+                    break;
+                }
+            };
+
+            report.entries += 1;
+
+            let path = entry.path().ok().map(Cow::into_owned);
+            let offset = entry.offset();
+            let size = entry.size();
+
+            let error = match io::copy(&mut entry, &mut io::sink()) {
+                Ok(n) if n == size => {
+                    report.bytes += n;
+                    continue;
+                }
+
+                Ok(_) => Error::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "entry is truncated",
+                )),
+
+                Err(e) => Error::Io(e),
+            };
+
+            if state.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            report.failures.push(Failure {
+                path,
+                offset: Some(offset),
+                error,
+            });
+
+            if !all {
+                return Ok(report);
+            }
+        }
+
+        if report.is_ok() {
+            if let Err(e) =
+                io::copy(&mut self.inner.into_reader(), &mut io::sink())
+            {
+                if state.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+
+                report.failures.push(Failure {
+                    path: None,
+                    offset: None,
+                    error: Error::Io(e),
+                });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl Inner {
+    /// Returns the decompressed stream, positioned after the entries that
+    /// have been read.
+    fn into_reader(self) -> Box<dyn Read> {
+        match self {
+            #[cfg(feature = "tar")]
+            Self::Tar(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "bzip2", feature = "tar"))]
+            Self::TarBzip2(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
+            Self::TarBzip2Rs(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "flate2", feature = "tar"))]
+            Self::TarGzip(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "lz4", feature = "tar"))]
+            Self::TarLz4(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXz(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "xz2", feature = "tar"))]
+            Self::TarXzSeekable(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstd(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(feature = "zstd", feature = "tar"))]
+            Self::TarZstdSeekable(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "flate2", feature = "xz2", feature = "zstd")
+            ))]
+            Self::TarParallel(archive) => Box::new(archive.into_inner()),
+
+            #[cfg(feature = "tar")]
+            Self::TarReadAhead(archive) => Box::new(archive.into_inner()),
+        }
+    }
+}
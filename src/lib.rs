@@ -5,6 +5,8 @@
 #![forbid(unsafe_code)]
 #![deny(clippy::all, missing_docs, unused_must_use)]
 #![warn(clippy::pedantic, clippy::nursery, clippy::cargo)]
+// ALLOW the digest crates depend on diverging versions of shared crates
+#![allow(clippy::multiple_crate_versions)]
 
 #[cfg(not(any(feature = "tar")))]
 compile_error!("there must be at least one archive file format feature");
@@ -13,16 +15,177 @@ compile_error!("there must be at least one archive file format feature");
 compile_error!("there must be only one bzip2 feature");
 
 mod archive;
+#[cfg(feature = "tar")]
+mod attributes;
+#[cfg(feature = "tar")]
+mod builder;
+#[cfg(feature = "tar")]
+mod canonical;
 #[cfg(feature = "clap")]
 pub mod clap;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub mod diff;
+#[cfg(feature = "tar")]
+mod edit;
 mod entries;
 mod entry;
 mod error;
+mod filter;
+mod integrity;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+mod manifest;
+#[cfg(feature = "tar")]
+mod merge;
+#[cfg(feature = "tar")]
+mod nested;
+mod options;
+#[cfg(all(
+    feature = "tar",
+    any(feature = "flate2", feature = "xz2", feature = "zstd")
+))]
+mod parallel;
+mod progress;
+#[cfg(feature = "tar")]
+mod read_ahead;
+#[cfg(feature = "regex")]
+mod search;
+#[cfg(all(feature = "tar", any(feature = "xz2", feature = "zstd")))]
+mod seekable;
+mod source;
+#[cfg(feature = "tar")]
+mod sparse;
+#[cfg(feature = "tar")]
+mod spool;
+#[cfg(feature = "tar")]
+mod subset;
 pub mod support;
+#[cfg(all(test, feature = "tar"))]
+mod testing;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+mod verify;
+mod vfs;
+mod volume;
 
 pub use archive::Archive;
+#[cfg(feature = "tar")]
+pub use attributes::AclEntry;
+#[cfg(feature = "tar")]
+pub use attributes::AclTag;
+#[cfg(feature = "tar")]
+pub use attributes::Attributes;
+#[cfg(feature = "tar")]
+pub use attributes::Xattr;
+#[cfg(all(feature = "xattr", target_os = "linux"))]
+pub use attributes::XattrPolicy;
+#[cfg(feature = "tar")]
+pub use builder::convert;
+#[cfg(feature = "tar")]
+pub use builder::source_date_epoch;
+#[cfg(feature = "tar")]
+pub use builder::Builder;
+#[cfg(feature = "tar")]
+pub use canonical::canonicalize;
+#[cfg(feature = "tar")]
+pub use canonical::Normalization;
+#[cfg(feature = "tar")]
+pub use edit::Edit;
 pub use entries::Entries;
 pub use entry::Entry;
 pub use entry::EntryType;
 pub use error::Error;
 pub(crate) use error::Result;
+#[cfg(feature = "glob")]
+pub use filter::Wildcards;
+pub use integrity::Failure;
+pub use integrity::TestReport;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub use manifest::digest;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub use manifest::Algorithm;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub use manifest::Manifest;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub use manifest::ManifestEntry;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub use manifest::ManifestFormat;
+#[cfg(feature = "tar")]
+pub use merge::Conflict;
+#[cfg(feature = "tar")]
+pub use merge::Merge;
+#[cfg(feature = "tar")]
+pub use nested::Nested;
+#[cfg(feature = "tar")]
+pub use nested::NestedEntry;
+pub use options::OpenOptions;
+pub use progress::CancellationToken;
+pub use progress::Progress;
+#[cfg(feature = "regex")]
+pub use search::Hit;
+#[cfg(feature = "regex")]
+pub use search::Search;
+#[cfg(feature = "tar")]
+pub use sparse::SparseFormat;
+#[cfg(feature = "tar")]
+pub use sparse::SparseMap;
+#[cfg(feature = "tar")]
+pub use sparse::SparseRegion;
+#[cfg(feature = "tar")]
+pub use subset::Subset;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub use verify::Mismatch;
+#[cfg(any(
+    feature = "blake3",
+    feature = "crc32fast",
+    feature = "md-5",
+    feature = "sha2"
+))]
+pub use verify::Verification;
+pub use vfs::ArchiveFs;
+pub use vfs::DirEntry;
+pub use vfs::Metadata;
+pub use volume::Volumes;
@@ -0,0 +1,594 @@
+//! Content hashing of archive entries and checksum manifests.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Archive, Error, Result};
+
+/// Size of the buffer entries are hashed with.
+const BUFFER_SIZE: usize = 64 << 10;
+
+/// Digest algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+// NONEXHAUSTIVE new features could add new algorithms
+#[non_exhaustive]
+pub enum Algorithm {
+    #[cfg(feature = "sha2")]
+    /// SHA-256
+    Sha256,
+
+    #[cfg(feature = "sha2")]
+    /// SHA-512
+    Sha512,
+
+    #[cfg(feature = "blake3")]
+    /// BLAKE3 with 256 bit output
+    Blake3,
+
+    #[cfg(feature = "crc32fast")]
+    /// CRC-32 as used by gzip and zip
+    Crc32,
+
+    #[cfg(feature = "md-5")]
+    /// MD5
+    Md5,
+}
+
+impl Algorithm {
+    /// Returns the algorithm name as used in BSD-style tags, like `SHA256`.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "sha2")]
+            Self::Sha256 => "SHA256",
+
+            #[cfg(feature = "sha2")]
+            Self::Sha512 => "SHA512",
+
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => "BLAKE3",
+
+            #[cfg(feature = "crc32fast")]
+            Self::Crc32 => "CRC32",
+
+            #[cfg(feature = "md-5")]
+            Self::Md5 => "MD5",
+        }
+    }
+
+    /// Returns all supported algorithms.
+    #[must_use]
+    pub const fn all<'a>() -> &'a [Self] {
+        &[
+            #[cfg(feature = "sha2")]
+            Self::Sha256,
+            #[cfg(feature = "sha2")]
+            Self::Sha512,
+            #[cfg(feature = "blake3")]
+            Self::Blake3,
+            #[cfg(feature = "crc32fast")]
+            Self::Crc32,
+            #[cfg(feature = "md-5")]
+            Self::Md5,
+        ]
+    }
+
+    /// Returns the algorithm with the given name, ignoring case, or `None`
+    /// if it is not supported.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            #[cfg(feature = "sha2")]
+            Self::Sha256 => Hasher::Sha256(sha2::Digest::new()),
+
+            #[cfg(feature = "sha2")]
+            Self::Sha512 => Hasher::Sha512(sha2::Digest::new()),
+
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => Hasher::Blake3(Box::default()),
+
+            #[cfg(feature = "crc32fast")]
+            Self::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+
+            #[cfg(feature = "md-5")]
+            Self::Md5 => Hasher::Md5(md5::Digest::new()),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Running digest of one algorithm.
+enum Hasher {
+    #[cfg(feature = "sha2")]
+    Sha256(sha2::Sha256),
+
+    #[cfg(feature = "sha2")]
+    Sha512(sha2::Sha512),
+
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+
+    #[cfg(feature = "crc32fast")]
+    Crc32(crc32fast::Hasher),
+
+    #[cfg(feature = "md-5")]
+    Md5(md5::Md5),
+}
+
+impl Hasher {
+    fn update(&mut self, buf: &[u8]) {
+        match self {
+            #[cfg(feature = "sha2")]
+            Self::Sha256(hasher) => sha2::Digest::update(hasher, buf),
+
+            #[cfg(feature = "sha2")]
+            Self::Sha512(hasher) => sha2::Digest::update(hasher, buf),
+
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => {
+                hasher.update(buf);
+            }
+
+            #[cfg(feature = "crc32fast")]
+            Self::Crc32(hasher) => hasher.update(buf),
+
+            #[cfg(feature = "md-5")]
+            Self::Md5(hasher) => md5::Digest::update(hasher, buf),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            #[cfg(feature = "sha2")]
+            Self::Sha256(hasher) => hex(&sha2::Digest::finalize(hasher)),
+
+            #[cfg(feature = "sha2")]
+            Self::Sha512(hasher) => hex(&sha2::Digest::finalize(hasher)),
+
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => hex(hasher.finalize().as_bytes()),
+
+            #[cfg(feature = "crc32fast")]
+            Self::Crc32(hasher) => hex(&hasher.finalize().to_be_bytes()),
+
+            #[cfg(feature = "md-5")]
+            Self::Md5(hasher) => hex(&md5::Digest::finalize(hasher)),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    use fmt::Write;
+
+    bytes.iter().fold(String::new(), |mut s, byte| {
+        let _ = write!(s, "{byte:02x}");
+        s
+    })
+}
+
+/// Returns the lowercase hex digests of `reader` with all `algorithms`,
+/// reading it only once.
+///
+/// # Errors
+///
+/// Returns an error if reading fails.
+pub fn digest(
+    mut reader: impl Read,
+    algorithms: &[Algorithm],
+) -> io::Result<BTreeMap<Algorithm, String>> {
+    let mut hashers: Vec<(Algorithm, Hasher)> = algorithms
+        .iter()
+        .map(|algorithm| (*algorithm, algorithm.hasher()))
+        .collect();
+
+    let mut buf = vec![0; BUFFER_SIZE];
+
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for (_, hasher) in &mut hashers {
+            hasher.update(&buf[..n]);
+        }
+    }
+
+    Ok(hashers
+        .into_iter()
+        .map(|(algorithm, hasher)| (algorithm, hasher.finalize()))
+        .collect())
+}
+
+/// Checksum manifest of the regular files of an archive.
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::{Algorithm, Archive, ManifestFormat};
+///
+/// let mut archive = Archive::open("backup.tar.zst")?;
+/// let manifest = archive.manifest(&[Algorithm::Sha256, Algorithm::Blake3])?;
+///
+/// manifest.write(std::io::stdout(), ManifestFormat::Sum(Algorithm::Sha256))?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    entries: Vec<ManifestEntry>,
+}
+
+/// Checksums of a single file of a [`Manifest`].
+// ALLOW this is not a public module
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// Size of the file in bytes, if known.
+    pub size: Option<u64>,
+
+    /// Lowercase hex digests of the file contents.
+    pub digests: BTreeMap<Algorithm, String>,
+}
+
+/// Text format of a [`Manifest`].
+// ALLOW this is not a public module
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// NONEXHAUSTIVE new formats could be added
+#[non_exhaustive]
+pub enum ManifestFormat {
+    /// Output of `sha256sum` and friends, i.e. `<digest>  <path>` with the
+    /// digests of the given algorithm.
+    Sum(Algorithm),
+
+    /// BSD-style tags, i.e. `SHA256 (<path>) = <digest>`, of all algorithms,
+    /// as written by `sha256sum --tag`.
+    Bsd,
+
+    /// JSON array of objects with `path`, `size` and one member per
+    /// algorithm, named by its lowercase name.
+    #[cfg(feature = "serde_json")]
+    Json,
+}
+
+impl Manifest {
+    /// Returns the entries of this manifest.
+    #[must_use]
+    pub fn entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+
+    /// Appends an entry.
+    pub fn push(&mut self, entry: ManifestEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Reads a manifest in `format` from `reader`.
+    ///
+    /// Digests of the same path on multiple lines, as in BSD-style tags of
+    /// several algorithms, are merged into one entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, if the manifest is malformed or if
+    /// it contains digests of unsupported algorithms.
+    pub fn read(reader: impl BufRead, format: ManifestFormat) -> Result<Self> {
+        match format {
+            ManifestFormat::Sum(algorithm) => {
+                Self::read_lines(reader, |line| {
+                    let (escaped, line) = unescape_prefix(line);
+
+                    let (digest, path) = line
+                        .split_once("  ")
+                        .or_else(|| line.split_once(" *"))
+                        .ok_or("expected `<digest>  <path>`")?;
+
+                    Ok((path_of(path, escaped), algorithm, digest.into()))
+                })
+            }
+
+            ManifestFormat::Bsd => Self::read_lines(reader, |line| {
+                let (escaped, line) = unescape_prefix(line);
+
+                let (name, line) = line
+                    .split_once(" (")
+                    .ok_or("expected `<algorithm> (<path>) = <digest>`")?;
+
+                let (path, digest) = line
+                    .rsplit_once(") = ")
+                    .ok_or("expected `<algorithm> (<path>) = <digest>`")?;
+
+                let algorithm = Algorithm::from_name(name)
+                    .ok_or("unsupported algorithm")?;
+
+                Ok((path_of(path, escaped), algorithm, digest.into()))
+            }),
+
+            #[cfg(feature = "serde_json")]
+            ManifestFormat::Json => Self::read_json(reader),
+        }
+    }
+
+    fn read_lines(
+        reader: impl BufRead,
+        parse: impl Fn(&str) -> std::result::Result<Line, &'static str>,
+    ) -> Result<Self> {
+        let mut manifest = Self::default();
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (path, algorithm, digest) =
+                parse(&line).map_err(|reason| {
+                    Error::InvalidManifest(format!(
+                        "line {}: {reason}",
+                        number + 1
+                    ))
+                })?;
+
+            let i = *index.entry(path.clone()).or_insert_with(|| {
+                manifest.push(ManifestEntry {
+                    path,
+                    size: None,
+                    digests: BTreeMap::new(),
+                });
+
+                manifest.entries.len() - 1
+            });
+
+            manifest.entries[i]
+                .digests
+                .insert(algorithm, digest.to_lowercase());
+        }
+
+        Ok(manifest)
+    }
+
+    #[cfg(feature = "serde_json")]
+    fn read_json(reader: impl BufRead) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidManifest(reason);
+
+        let entries: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_reader(reader)
+                .map_err(|e| invalid(e.to_string()))?;
+
+        let mut manifest = Self::default();
+
+        for (i, object) in entries.into_iter().enumerate() {
+            let mut path = None;
+            let mut size = None;
+            let mut digests = BTreeMap::new();
+
+            for (key, value) in object {
+                match (key.as_str(), value) {
+                    ("path", serde_json::Value::String(value)) => {
+                        path = Some(PathBuf::from(value));
+                    }
+
+                    ("size", serde_json::Value::Number(value)) => {
+                        size = value.as_u64();
+                    }
+
+                    (name, serde_json::Value::String(value)) => {
+                        let algorithm = Algorithm::from_name(name)
+                            .ok_or_else(|| {
+                                invalid(format!(
+                                    "entry {i}: unsupported algorithm {name}"
+                                ))
+                            })?;
+
+                        digests.insert(algorithm, value.to_lowercase());
+                    }
+
+                    (name, _) => {
+                        return Err(invalid(format!(
+                            "entry {i}: unexpected value of {name}"
+                        )));
+                    }
+                }
+            }
+
+            let path =
+                path.ok_or_else(|| invalid(format!("entry {i}: no path")))?;
+
+            manifest.push(ManifestEntry {
+                path,
+                size,
+                digests,
+            });
+        }
+
+        Ok(manifest)
+    }
+
+    /// Writes this manifest in `format` to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails or, for [`ManifestFormat::Sum`], if
+    /// an entry lacks the digest of the algorithm.
+    pub fn write(
+        &self,
+        mut writer: impl Write,
+        format: ManifestFormat,
+    ) -> io::Result<()> {
+        match format {
+            ManifestFormat::Sum(algorithm) => {
+                for entry in &self.entries {
+                    let digest =
+                        entry.digests.get(&algorithm).ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!(
+                                    "no {algorithm} digest of {}",
+                                    entry.path.display()
+                                ),
+                            )
+                        })?;
+
+                    let (escaped, path) = escape(&entry.path);
+                    let prefix = if escaped { "\\" } else { "" };
+
+                    writeln!(writer, "{prefix}{digest}  {path}")?;
+                }
+            }
+
+            ManifestFormat::Bsd => {
+                for entry in &self.entries {
+                    let (escaped, path) = escape(&entry.path);
+                    let prefix = if escaped { "\\" } else { "" };
+
+                    for (algorithm, digest) in &entry.digests {
+                        writeln!(
+                            writer,
+                            "{prefix}{algorithm} ({path}) = {digest}"
+                        )?;
+                    }
+                }
+            }
+
+            #[cfg(feature = "serde_json")]
+            ManifestFormat::Json => {
+                let entries: Vec<serde_json::Value> = self
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        let mut object = serde_json::Map::new();
+
+                        object.insert(
+                            "path".into(),
+                            entry.path.to_string_lossy().into(),
+                        );
+
+                        if let Some(size) = entry.size {
+                            object.insert("size".into(), size.into());
+                        }
+
+                        for (algorithm, digest) in &entry.digests {
+                            object.insert(
+                                algorithm.name().to_lowercase(),
+                                digest.as_str().into(),
+                            );
+                        }
+
+                        object.into()
+                    })
+                    .collect();
+
+                serde_json::to_writer_pretty(&mut writer, &entries)?;
+                writeln!(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the path escaped like coreutils does for file names containing
+/// backslashes or line breaks, and whether it had to be escaped.
+fn escape(path: &Path) -> (bool, String) {
+    let path = path.to_string_lossy();
+
+    if !path.contains(['\\', '\n', '\r']) {
+        return (false, path.into_owned());
+    }
+
+    let escaped = path
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+
+    (true, escaped)
+}
+
+/// Path, algorithm and digest of a single line of a manifest.
+type Line = (PathBuf, Algorithm, String);
+
+/// Strips the leading backslash of lines with escaped paths.
+fn unescape_prefix(line: &str) -> (bool, &str) {
+    line.strip_prefix('\\')
+        .map_or((false, line), |line| (true, line))
+}
+
+/// Returns the path, unescaping it if it has been escaped with [`escape`].
+fn path_of(path: &str, escaped: bool) -> PathBuf {
+    if !escaped {
+        return PathBuf::from(path);
+    }
+
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    PathBuf::from(unescaped)
+}
+
+impl Archive {
+    /// Returns the checksum manifest of all regular files of this archive,
+    /// including sparse files, hashing each file with all `algorithms` in a
+    /// single pass.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the archive fails.
+    pub fn manifest(&mut self, algorithms: &[Algorithm]) -> Result<Manifest> {
+        let mut manifest = Manifest::default();
+
+        for entry in self.entries()? {
+            let mut entry = entry?;
+
+            if !entry.entry_type().is_file_or_sparse() {
+                continue;
+            }
+
+            let path = entry.path()?.into_owned();
+            let size = entry.size();
+            let digests = digest(&mut entry, algorithms)?;
+
+            manifest.push(ManifestEntry {
+                path,
+                size: Some(size),
+                digests,
+            });
+        }
+
+        Ok(manifest)
+    }
+}
@@ -0,0 +1,524 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::entry::normalize;
+use crate::spool::Spool;
+use crate::support::Format;
+use crate::{Archive, Builder, EntryType, Error, Result};
+
+/// What to do if entries of different inputs of a [`Merge`] have the same
+/// path.
+///
+/// Directories never conflict with each other, the first one is kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Conflict {
+    /// Keeps the entry of the earliest input.
+    #[default]
+    FirstWins,
+
+    /// Keeps the entry of the latest input, at the position of that entry.
+    ///
+    /// The contents of all entries are spooled to an anonymous temporary
+    /// file first, which needs as much space as the uncompressed inputs.
+    LastWins,
+
+    /// Fails with [`Error::Conflict`].
+    Error,
+
+    /// Keeps all entries, appending `.1`, `.2` and so on to the path of
+    /// later ones.
+    Rename,
+}
+
+/// Merger of several archives into one.
+///
+/// Entries are written in the order of the inputs, each input with an
+/// optional path prefix. If a file is dropped because of a conflict, its
+/// contents are spooled to an anonymous temporary file, and the first hard
+/// link of its input to it is written as a regular file with them instead,
+/// to which later hard links point.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// use archive_rs::support::Format;
+/// use archive_rs::{Archive, Conflict, Merge};
+///
+/// let dst = BufWriter::new(File::create("dist.tar.zst")?);
+///
+/// Merge::new()
+///     .conflict(Conflict::Error)
+///     .input_with_prefix(Archive::open("server.tar.gz")?, "dist/server")
+///     .input_with_prefix(Archive::open("client.tar.xz")?, "dist/client")
+///     .input(Archive::open("docs.tar")?)
+///     .write(Format::TarZstd, dst)?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+#[must_use]
+pub struct Merge {
+    inputs: Vec<(Archive, PathBuf)>,
+    conflict: Conflict,
+}
+
+/// Where an entry of an input is in the merged archive.
+enum Moved {
+    /// Written at the path.
+    Written(PathBuf),
+
+    /// Dropped file, whose contents are at an offset in the spool with a
+    /// size.
+    Spooled(u64, u64),
+
+    /// Dropped entry without contents.
+    Dropped,
+}
+
+/// Entry whose contents have been spooled.
+struct Record {
+    path: PathBuf,
+    header: tar::Header,
+    extensions: Vec<(String, Vec<u8>)>,
+    link_name: Option<PathBuf>,
+    offset: u64,
+
+    /// Index of the spooled entry a hard link points to.
+    target: Option<usize>,
+}
+
+impl Merge {
+    /// Returns a merger without inputs that keeps the first of conflicting
+    /// entries.
+    pub const fn new() -> Self {
+        Self {
+            inputs: vec![],
+            conflict: Conflict::FirstWins,
+        }
+    }
+
+    /// Sets what to do with conflicting entries.
+    pub const fn conflict(mut self, conflict: Conflict) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    /// Adds the entries of `archive` at their paths.
+    pub fn input(mut self, archive: Archive) -> Self {
+        self.inputs.push((archive, PathBuf::new()));
+        self
+    }
+
+    /// Adds the entries of `archive` below `prefix`.
+    pub fn input_with_prefix(
+        mut self,
+        archive: Archive,
+        prefix: impl AsRef<Path>,
+    ) -> Self {
+        self.inputs.push((archive, normalize(prefix.as_ref())));
+        self
+    }
+
+    /// Writes the entries of all inputs as an archive of `format` to
+    /// `writer`, and returns the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `format` cannot be written, if reading an input
+    /// or writing fails, or if entries conflict with [`Conflict::Error`], in
+    /// which case the written archive is incomplete.
+    pub fn write<W: Write>(self, format: Format, writer: W) -> Result<W> {
+        let builder = Builder::new(writer, format)?;
+
+        if self.conflict == Conflict::LastWins {
+            self.write_last_wins(builder)
+        } else {
+            self.write_streaming(builder)
+        }
+    }
+
+    fn write_streaming<W: Write>(
+        mut self,
+        mut builder: Builder<W>,
+    ) -> Result<W> {
+        // written paths and whether they are directories
+        let mut written: HashMap<PathBuf, bool> = HashMap::new();
+        let mut spool = Spool::new()?;
+
+        for (archive, prefix) in &mut self.inputs {
+            // where the entries of this input are in the merged archive
+            let mut moved: HashMap<PathBuf, Moved> = HashMap::new();
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let original = normalize(&entry.path()?);
+                let mut path = prefix.join(&original);
+
+                // the root of an input without a prefix
+                if path.as_os_str().is_empty() {
+                    continue;
+                }
+
+                let is_dir = entry.entry_type() == EntryType::Directory;
+
+                let mut link_name = entry.link_name()?.map(Cow::into_owned);
+
+                // target and contents of a hard link to a dropped file
+                let mut unlinked = None;
+
+                if entry.entry_type() == EntryType::Link {
+                    let Some(target) = &link_name else {
+                        continue;
+                    };
+
+                    let target = normalize(target);
+
+                    link_name = match moved.get(&target) {
+                        Some(Moved::Written(target)) => Some(target.clone()),
+                        Some(&Moved::Spooled(offset, size)) => {
+                            unlinked = Some((target, offset, size));
+                            None
+                        }
+                        Some(Moved::Dropped) => continue,
+                        None => Some(prefix.join(target)),
+                    };
+                }
+
+                if let Some(&was_dir) = written.get(&path) {
+                    if is_dir && was_dir {
+                        moved.insert(original, Moved::Written(path));
+                        continue;
+                    }
+
+                    match self.conflict {
+                        Conflict::Error => return Err(Error::Conflict(path)),
+
+                        Conflict::Rename => {
+                            path = unique(&path, |path| {
+                                written.contains_key(path)
+                            });
+                        }
+
+                        Conflict::FirstWins | Conflict::LastWins => {
+                            // a duplicate within this input keeps its links
+                            if moved.contains_key(&original) {
+                                continue;
+                            }
+
+                            let dropped = if let Some((_, offset, size)) =
+                                unlinked
+                            {
+                                Moved::Spooled(offset, size)
+                            } else if entry.entry_type() == EntryType::Link {
+                                link_name
+                                    .map_or(Moved::Dropped, Moved::Written)
+                            } else if entry.entry_type().is_file_or_sparse() {
+                                let size = entry.size();
+                                Moved::Spooled(spool.push(&mut entry)?, size)
+                            } else {
+                                Moved::Dropped
+                            };
+
+                            moved.insert(original, dropped);
+                            continue;
+                        }
+                    }
+                }
+
+                written.insert(path.clone(), is_dir);
+                moved.insert(original, Moved::Written(path.clone()));
+
+                if let Some((target, offset, size)) = unlinked {
+                    builder.append_unlinked(
+                        entry.header().clone(),
+                        &entry.pax_extensions()?,
+                        &path,
+                        size,
+                        spool.reader(offset, size)?,
+                    )?;
+
+                    // later hard links point to the first one
+                    moved.insert(target, Moved::Written(path));
+                    continue;
+                }
+
+                let mut header = entry.header().clone();
+                header.set_size(entry.size());
+                let extensions = entry.pax_extensions()?;
+
+                builder.append_header(
+                    header,
+                    &extensions,
+                    &path,
+                    link_name.as_deref(),
+                    &mut entry,
+                )?;
+            }
+        }
+
+        builder.finish()
+    }
+
+    fn write_last_wins<W: Write>(
+        mut self,
+        mut builder: Builder<W>,
+    ) -> Result<W> {
+        let mut spool = Spool::new()?;
+        let mut spooled: Vec<Record> = vec![];
+
+        // index of the spooled entry that is written for each path
+        let mut winners: HashMap<PathBuf, usize> = HashMap::new();
+
+        for (archive, prefix) in &mut self.inputs {
+            // indices of the spooled entries of this input
+            let mut indices: HashMap<PathBuf, usize> = HashMap::new();
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let original = normalize(&entry.path()?);
+                let path = prefix.join(&original);
+
+                // the root of an input without a prefix
+                if path.as_os_str().is_empty() {
+                    continue;
+                }
+
+                let index = spooled.len();
+
+                let mut link_name = entry.link_name()?.map(Cow::into_owned);
+                let mut target = None;
+
+                if entry.entry_type() == EntryType::Link {
+                    if let Some(name) = &link_name {
+                        let name = normalize(name);
+                        target = indices.get(&name).copied();
+                        link_name = Some(prefix.join(name));
+                    }
+                }
+
+                let is_dir = entry.entry_type() == EntryType::Directory;
+
+                let keeps_dir = winners.get(&path).is_some_and(|winner| {
+                    is_dir && spooled[*winner].header.entry_type().is_dir()
+                });
+
+                if !keeps_dir {
+                    winners.insert(path.clone(), index);
+                }
+
+                indices.insert(original, index);
+
+                let mut header = entry.header().clone();
+                header.set_size(entry.size());
+
+                let offset = spool.push(&mut entry)?;
+
+                spooled.push(Record {
+                    path,
+                    header,
+                    extensions: entry.pax_extensions()?,
+                    link_name,
+                    offset,
+                    target,
+                });
+            }
+        }
+
+        let mut spool = spool.finish()?;
+
+        // paths of the first hard links to replaced entries, which are
+        // written with their contents
+        let mut unlinked: HashMap<usize, PathBuf> = HashMap::new();
+
+        for (index, entry) in spooled.iter().enumerate() {
+            if winners.get(&entry.path) != Some(&index) {
+                continue;
+            }
+
+            if let Some(mut target) = entry.target {
+                // hard links to hard links have the contents of their target
+                while let Some(next) = spooled[target].target {
+                    target = next;
+                }
+
+                if winners.get(&spooled[target].path) != Some(&target) {
+                    if let Some(path) = unlinked.get(&target) {
+                        builder.append_header(
+                            entry.header.clone(),
+                            &entry.extensions,
+                            &entry.path,
+                            Some(path),
+                            io::empty(),
+                        )?;
+                    } else {
+                        let size = spooled[target].header.size()?;
+
+                        builder.append_unlinked(
+                            entry.header.clone(),
+                            &entry.extensions,
+                            &entry.path,
+                            size,
+                            spool.read(spooled[target].offset, size)?,
+                        )?;
+
+                        unlinked.insert(target, entry.path.clone());
+                    }
+
+                    continue;
+                }
+            }
+
+            let data = spool.read(entry.offset, entry.header.size()?)?;
+
+            builder.append_header(
+                entry.header.clone(),
+                &entry.extensions,
+                &entry.path,
+                entry.link_name.as_deref(),
+                data,
+            )?;
+        }
+
+        builder.finish()
+    }
+}
+
+impl Default for Merge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `path` with the first suffix `.1`, `.2` and so on for which
+/// `exists` returns false.
+fn unique(path: &Path, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let mut n = 1_u64;
+
+    loop {
+        let mut candidate = path.as_os_str().to_owned();
+        candidate.push(format!(".{n}"));
+        let candidate = PathBuf::from(candidate);
+
+        if !exists(&candidate) {
+            return candidate;
+        }
+
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tar::EntryType;
+
+    use super::*;
+    use crate::testing::{self, Listed};
+
+    fn root_and_file() -> Vec<u8> {
+        testing::tarball(|builder| {
+            testing::entry(builder, EntryType::Directory, "./", b"");
+            testing::file(builder, "./a", b"a");
+        })
+    }
+
+    #[test]
+    fn skips_root_entries() {
+        for conflict in [Conflict::FirstWins, Conflict::LastWins] {
+            let (_file, archive) = testing::open(&root_and_file(), ".tar");
+
+            let merged = Merge::new()
+                .conflict(conflict)
+                .input(archive)
+                .write(Format::Tar, vec![])
+                .unwrap();
+
+            let paths: Vec<_> = testing::list(&merged)
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect();
+
+            assert_eq!(paths, ["a"]);
+        }
+    }
+
+    #[test]
+    fn keeps_root_entries_below_a_prefix() {
+        let (_file, archive) = testing::open(&root_and_file(), ".tar");
+
+        let merged = Merge::new()
+            .input_with_prefix(archive, "dist")
+            .write(Format::Tar, vec![])
+            .unwrap();
+
+        let paths: Vec<_> = testing::list(&merged)
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+
+        assert_eq!(paths, ["dist/", "dist/a"]);
+    }
+
+    /// Returns a tarball with the file `a` with contents `data` and the
+    /// hard links `b` and `c` to it.
+    fn file_and_links(data: &[u8]) -> Vec<u8> {
+        testing::tarball(|builder| {
+            testing::file(builder, "a", data);
+            testing::link(builder, EntryType::Link, "b", "a");
+            testing::link(builder, EntryType::Link, "c", "a");
+        })
+    }
+
+    #[test]
+    fn hard_links_to_dropped_files_hold_the_contents() {
+        let first = testing::tarball(|builder| {
+            testing::file(builder, "a", b"1");
+        });
+
+        let (_first, first) = testing::open(&first, ".tar");
+        let (_second, second) = testing::open(&file_and_links(b"2"), ".tar");
+
+        let merged = Merge::new()
+            .input(first)
+            .input(second)
+            .write(Format::Tar, vec![])
+            .unwrap();
+
+        assert_eq!(
+            testing::list(&merged),
+            [
+                Listed::new("a", EntryType::Regular, b"1", None),
+                Listed::new("b", EntryType::Regular, b"2", None),
+                Listed::new("c", EntryType::Link, b"", Some("b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn hard_links_to_replaced_files_hold_the_contents() {
+        let second = testing::tarball(|builder| {
+            testing::file(builder, "a", b"2");
+        });
+
+        let (_first, first) = testing::open(&file_and_links(b"1"), ".tar");
+        let (_second, second) = testing::open(&second, ".tar");
+
+        let merged = Merge::new()
+            .conflict(Conflict::LastWins)
+            .input(first)
+            .input(second)
+            .write(Format::Tar, vec![])
+            .unwrap();
+
+        assert_eq!(
+            testing::list(&merged),
+            [
+                Listed::new("b", EntryType::Regular, b"1", None),
+                Listed::new("c", EntryType::Link, b"", Some("b")),
+                Listed::new("a", EntryType::Regular, b"2", None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use std::ffi::OsString;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "bzip2")]
+use bzip2::read::BzDecoder as BzSysDecoder;
+#[cfg(feature = "bzip2-rs")]
+use bzip2_rs::decoder::DecoderReader as BzNativeDecoder;
+#[cfg(feature = "flate2")]
+use flate2::read::MultiGzDecoder;
+#[cfg(feature = "lz4")]
+use lz4::Decoder as Lz4Decoder;
+#[cfg(feature = "xz2")]
+use xz2::read::XzDecoder;
+#[cfg(feature = "zstd")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use crate::source::{is_header, read_full, State, Tracked, BLOCK};
+use crate::support::Format;
+use crate::{Archive, Entry, Error, Result};
+
+/// Default maximum depth of nested archives.
+const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// Separator of the paths of nested archives and their entries in virtual
+/// paths.
+const SEPARATOR: &str = "!/";
+
+/// Recursive traversal of an archive and the archives nested in it.
+///
+/// Regular files whose name or magic bytes match a supported [`Format`] and
+/// that start with a valid tar header are opened as archives, and their
+/// entries are visited instead of them, up to a maximum depth. Nested
+/// archives are decompressed while reading the entry, all supported formats
+/// can be read sequentially without spilling them to a temporary file.
+///
+/// Each entry is visited with a virtual path, the path of the archive and the
+/// paths of the nested archives and the entry, separated by `!/`.
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::{Archive, Nested};
+///
+/// let mut archive = Archive::open("outer.tar.gz")?;
+///
+/// Nested::new().max_depth(3).for_each(&mut archive, |entry| {
+///     // e.g. outer.tar.gz!/lib/plugins.tar.xz!/META-INF/MANIFEST.MF
+///     println!("{}", entry.path().display());
+///     Ok(())
+/// })?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+#[must_use]
+pub struct Nested {
+    max_depth: usize,
+}
+
+/// Entry visited by [`Nested::for_each`].
+///
+/// The contents are read from this entry, not via [`NestedEntry::entry`],
+/// because the first bytes may already have been read to detect archives.
+// ALLOW this is not a public module
+#[allow(clippy::module_name_repetitions)]
+pub struct NestedEntry<'a, 'b> {
+    path: PathBuf,
+    depth: usize,
+    head: Cursor<Vec<u8>>,
+    entry: &'b mut Entry<'a>,
+}
+
+/// Visitor of [`Nested::for_each`].
+type Visitor<'f> = dyn FnMut(&mut NestedEntry<'_, '_>) -> Result<()> + 'f;
+
+impl Nested {
+    /// Returns a traversal that opens archives nested up to 8 levels deep.
+    pub const fn new() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Sets how deep nested archives are opened, `0` visits only the entries
+    /// of the archive itself.
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Calls `visit` with all entries of `archive` and of the archives
+    /// nested in it, in the order they are stored.
+    ///
+    /// Nested archives that turn out to be corrupt do not stop the traversal,
+    /// they are returned with their virtual paths and the error of reading
+    /// them, after their entries up to the error have been visited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading `archive` itself fails or the first error
+    /// returned by `visit`.
+    pub fn for_each(
+        &self,
+        archive: &mut Archive,
+        mut visit: impl FnMut(&mut NestedEntry<'_, '_>) -> Result<()>,
+    ) -> Result<Vec<(PathBuf, Error)>> {
+        let root = archive.paths.first().cloned().unwrap_or_default();
+        let mut corrupt = vec![];
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = join(&root, &entry.path()?);
+
+            self.visit(&mut entry, path, 0, &mut visit, &mut corrupt)??;
+        }
+
+        Ok(corrupt)
+    }
+
+    /// Visits `entry` at `path`, or the entries of the archive it is, and
+    /// returns the result of visiting, or an error if reading `entry` fails.
+    fn visit(
+        &self,
+        entry: &mut Entry<'_>,
+        path: PathBuf,
+        depth: usize,
+        visit: &mut Visitor<'_>,
+        corrupt: &mut Vec<(PathBuf, Error)>,
+    ) -> Result<Result<()>> {
+        let mut head = vec![];
+
+        let format = if depth < self.max_depth && entry.entry_type().is_file()
+        {
+            detect(entry, &mut head)?
+        } else {
+            None
+        };
+
+        let Some(format) = format else {
+            return Ok(visit(&mut NestedEntry {
+                path,
+                depth,
+                head: Cursor::new(head),
+                entry,
+            }));
+        };
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "nested",
+            path = %path.display(),
+            format = format.name(),
+        )
+        .entered();
+
+        let visited = decoder(format, Cursor::new(head).chain(entry))
+            .map_err(Error::from)
+            .and_then(|reader| {
+                self.visit_archive(reader, &path, depth, visit, corrupt)
+            });
+
+        match visited {
+            Ok(visited) => Ok(visited),
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, "reading nested archive failed");
+
+                corrupt.push((path, e));
+                Ok(Ok(()))
+            }
+        }
+    }
+
+    /// Visits the entries of the nested archive at `path` read from
+    /// `reader`, and returns the result of visiting them, or an error if
+    /// reading the archive fails.
+    fn visit_archive(
+        &self,
+        mut reader: Box<dyn Read + '_>,
+        path: &Path,
+        depth: usize,
+        visit: &mut Visitor<'_>,
+        corrupt: &mut Vec<(PathBuf, Error)>,
+    ) -> Result<Result<()>> {
+        let reader: &mut dyn Read = &mut reader;
+        let state = State::new(None);
+        let mut archive = tar::Archive::new(Tracked::new(reader, &state));
+        let mut next = 0;
+
+        for nested in archive.entries()? {
+            let mut nested = Entry::from_tar(nested, next, Entry::TarNested)?;
+            let nested_path = join(path, &nested.path()?);
+
+            // the headers of the next entry follow the data of this one
+            let size = nested.stored_size()?;
+            next =
+                state.tarball_position() + size.next_multiple_of(BLOCK as u64);
+
+            // reading the nested entry fails if this archive is corrupt
+            let visited = self.visit(
+                &mut nested,
+                nested_path,
+                depth + 1,
+                visit,
+                corrupt,
+            )?;
+
+            if visited.is_err() {
+                return Ok(visited);
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+impl Default for Nested {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> NestedEntry<'a, '_> {
+    /// Returns the virtual path of the entry, e.g.
+    /// `outer.tar.gz!/lib/inner.tar!/README`.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns how deep the archive of the entry is nested, `0` for entries
+    /// of the archive itself.
+    #[must_use]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the entry, to inspect its metadata.
+    #[must_use]
+    pub const fn entry(&self) -> &Entry<'a> {
+        self.entry
+    }
+}
+
+impl Read for NestedEntry<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.head.read(buf)? {
+            0 => self.entry.read(buf),
+            n => Ok(n),
+        }
+    }
+}
+
+/// Returns `path` of an entry of the archive at `archive` as virtual path.
+fn join(archive: &Path, path: &Path) -> PathBuf {
+    let mut joined = OsString::from(archive);
+    joined.push(SEPARATOR);
+    joined.push(path);
+    PathBuf::from(joined)
+}
+
+/// Returns the format of the archive `entry` if it is one, reading its first
+/// bytes into `head`.
+///
+/// The format is derived from the name of the entry, or from its magic bytes
+/// if the name does not match a format or the contents do not match the name.
+fn detect(
+    entry: &mut Entry<'_>,
+    head: &mut Vec<u8>,
+) -> Result<Option<Format>> {
+    let by_name = Format::from_path(entry.path()?);
+
+    head.resize(BLOCK, 0);
+    let n = read_full(entry, head)?;
+    head.truncate(n);
+
+    let by_magic = Format::from_magic(head).filter(|&f| Some(f) != by_name);
+
+    for format in by_name.into_iter().chain(by_magic) {
+        if starts_with_header(format, &mut *entry, head) {
+            return Ok(Some(format));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns true if the contents in `format` that start with `head` and go
+/// on in `reader` start with a tar header, appending what is read to `head`.
+fn starts_with_header(
+    format: Format,
+    reader: impl Read,
+    head: &mut Vec<u8>,
+) -> bool {
+    // the compressed data needed to decompress the first header is kept in
+    // `head`, the entry cannot be read again
+    let start = Cursor::new(head.clone());
+    let tee = Tee { reader, copy: head };
+
+    let mut block = vec![0; BLOCK];
+
+    let header = decoder(format, start.chain(tee))
+        .and_then(|mut reader| read_full(&mut reader, &mut block));
+
+    matches!(header, Ok(n) if n == BLOCK && is_header(&block))
+}
+
+/// Returns a reader of the tarball in `format` read from `reader`.
+// ALLOW only some decoders fail to read the stream header
+#[allow(clippy::unnecessary_wraps)]
+fn decoder<'r>(
+    format: Format,
+    reader: impl Read + 'r,
+) -> io::Result<Box<dyn Read + 'r>> {
+    Ok(match format {
+        #[cfg(feature = "tar")]
+        Format::Tar => Box::new(reader),
+
+        #[cfg(all(feature = "bzip2", feature = "tar"))]
+        Format::TarBzip2 => Box::new(BzSysDecoder::new(reader)),
+
+        #[cfg(all(feature = "bzip2-rs", feature = "tar"))]
+        Format::TarBzip2 => Box::new(BzNativeDecoder::new(reader)),
+
+        #[cfg(all(feature = "flate2", feature = "tar"))]
+        Format::TarGzip => Box::new(MultiGzDecoder::new(reader)),
+
+        #[cfg(all(feature = "lz4", feature = "tar"))]
+        Format::TarLz4 => Box::new(Lz4Decoder::new(reader)?),
+
+        #[cfg(all(feature = "xz2", feature = "tar"))]
+        Format::TarXz => Box::new(XzDecoder::new(reader)),
+
+        #[cfg(all(feature = "zstd", feature = "tar"))]
+        Format::TarZstd => Box::new(ZstdDecoder::new(reader)?),
+    })
+}
+
+/// Reader that keeps a copy of everything read.
+struct Tee<'a, R> {
+    reader: R,
+    copy: &'a mut Vec<u8>,
+}
+
+impl<R: Read> Read for Tee<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.copy.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn corrupt_nested_entry_does_not_abort() {
+        let inner = testing::tarball(|builder| {
+            testing::file(builder, "x", &[b'x'; 4096]);
+        });
+
+        // the header is intact, the contents of `x` are cut off
+        let mut zstd = zstd::encode_all(&inner[..BLOCK], 0).unwrap();
+        let rest = zstd::encode_all(&inner[BLOCK..], 0).unwrap();
+        zstd.extend_from_slice(&rest[..rest.len() / 2]);
+
+        let outer = testing::tarball(|builder| {
+            testing::file(builder, "inner.tar.zst", &zstd);
+            testing::file(builder, "after", b"after");
+        });
+
+        let (file, mut archive) = testing::open(&outer, ".tar");
+        let mut visited = vec![];
+
+        let corrupt = Nested::new()
+            .for_each(&mut archive, |entry| {
+                visited.push(entry.path().to_owned());
+                Ok(())
+            })
+            .unwrap();
+
+        let inner = join(file.path(), Path::new("inner.tar.zst"));
+        let corrupt: Vec<_> =
+            corrupt.into_iter().map(|(path, _)| path).collect();
+
+        assert_eq!(corrupt, [inner]);
+        assert_eq!(visited, [join(file.path(), Path::new("after"))]);
+    }
+
+    #[test]
+    fn visitor_errors_abort() {
+        let inner = testing::tarball(|builder| {
+            testing::file(builder, "x", b"x");
+        });
+
+        let outer = testing::tarball(|builder| {
+            testing::file(builder, "inner.tar", &inner);
+            testing::file(builder, "after", b"after");
+        });
+
+        let (_file, mut archive) = testing::open(&outer, ".tar");
+        let mut visited = 0;
+
+        let result = Nested::new().for_each(&mut archive, |_| {
+            visited += 1;
+            Err(Error::Conflict(PathBuf::new()))
+        });
+
+        assert!(matches!(result, Err(Error::Conflict(_))));
+        assert_eq!(visited, 1);
+    }
+}
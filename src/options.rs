@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+use crate::volume::volumes;
+use crate::{Archive, CancellationToken, Result};
+
+/// Default memory limit of parallel decompression.
+const DEFAULT_MEMORY_LIMIT: u64 = 256 << 20;
+
+/// Options and flags which can be used to configure how an archive is opened.
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::OpenOptions;
+///
+/// let mut archive = OpenOptions::new().threads(8).open("backup.tar.zst")?;
+///
+/// for entry in archive.entries()? {
+///     println!("{}", entry?.path()?.display());
+/// }
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+// ALLOW this is not a public module
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct OpenOptions {
+    pub(crate) threads: usize,
+    pub(crate) memory_limit: u64,
+    pub(crate) read_ahead: usize,
+    pub(crate) cancellation: Option<CancellationToken>,
+}
+
+impl OpenOptions {
+    /// Returns the default options, which decompress on the calling thread.
+    pub const fn new() -> Self {
+        Self {
+            threads: 1,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            read_ahead: 0,
+            cancellation: None,
+        }
+    }
+
+    /// Sets the number of threads used for decompression.
+    ///
+    /// With more than one thread, multi-frame zstd, multi-block xz and
+    /// multi-member gzip in the [BGZF] layout are decompressed in parallel.
+    /// Single-frame inputs of these formats are decompressed sequentially on
+    /// a separate thread, other formats are not affected.
+    ///
+    /// [BGZF]: https://samtools.github.io/hts-specs/SAMv1.pdf
+    pub const fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the number of bytes of compressed and decompressed data that
+    /// parallel decompression keeps in flight at most, defaults to 256 MiB.
+    ///
+    /// Frames or blocks that exceed this limit on their own, including their
+    /// uncompressed size, are decompressed sequentially, as are zstd frames
+    /// that do not store their uncompressed size.
+    pub const fn memory_limit(&mut self, bytes: u64) -> &mut Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Sets the number of buffers of the read-ahead pipeline, disabled by
+    /// default.
+    ///
+    /// If enabled, the archive file is read and decompressed on a dedicated
+    /// thread into a bounded ring of `buffers` buffers of 256 KiB each, so
+    /// that decompression overlaps with processing the entries, e.g. hashing
+    /// or writing them. Parallel decompression, see [`OpenOptions::threads`],
+    /// takes precedence, and random access with
+    /// [`Archive::read_entry_at`] falls back to streaming.
+    pub const fn read_ahead(&mut self, buffers: usize) -> &mut Self {
+        self.read_ahead = buffers;
+        self
+    }
+
+    /// Sets the token to cancel processing of the archive, see
+    /// [`CancellationToken`].
+    pub fn cancellation_token(
+        &mut self,
+        token: CancellationToken,
+    ) -> &mut Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Opens the archive at `path` with these options.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if archive is of unsupported format or if opening it
+    /// fails.
+    pub fn open(&self, path: impl AsRef<Path>) -> Result<Archive> {
+        Archive::open_with(&volumes(path.as_ref()), self)
+    }
+
+    /// Opens the archive that consists of the volumes at `paths` with these
+    /// options, see [`Archive::open_volumes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if archive is of unsupported format or if opening it
+    /// fails.
+    pub fn open_volumes(
+        &self,
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> Result<Archive> {
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|path| path.as_ref().to_owned())
+            .collect();
+
+        Archive::open_with(&paths, self)
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
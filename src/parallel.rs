@@ -0,0 +1,636 @@
+//! Parallel decompression of compressed tarballs that consist of
+//! independently compressed units: zstd frames, xz blocks and gzip members.
+//!
+//! A dispatcher thread reads the compressed file sequentially and splits it
+//! into units, which a pool of worker threads decompresses. The reader
+//! reassembles the decompressed units in order. Units whose boundaries or
+//! uncompressed sizes are unknown, or that exceed the memory limit on their
+//! own, are decompressed sequentially by the dispatcher instead.
+
+use std::collections::BTreeMap;
+#[cfg(any(feature = "flate2", feature = "zstd"))]
+use std::io::BufRead;
+use std::io::{self, BufReader, Cursor, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+#[cfg(feature = "xz2")]
+use std::io::{Seek, SeekFrom};
+
+#[cfg(feature = "xz2")]
+use crate::seekable::{self, Block};
+use crate::source::Source;
+
+/// Size of the chunks emitted when decompressing sequentially.
+const CHUNK_SIZE: u64 = 1 << 20;
+
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: u32 = 0xFD2F_B528;
+#[cfg(feature = "zstd")]
+const ZSTD_SKIPPABLE_MASK: u32 = 0xFFFF_FFF0;
+#[cfg(feature = "zstd")]
+const ZSTD_SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+
+/// Compression codec of a parallel reader.
+#[derive(Clone, Copy, Debug)]
+pub enum Codec {
+    /// Multi-member gzip, members in the [BGZF] layout are decompressed in
+    /// parallel.
+    ///
+    /// [BGZF]: https://samtools.github.io/hts-specs/SAMv1.pdf
+    #[cfg(feature = "flate2")]
+    Gzip,
+
+    /// Multi-block xz.
+    #[cfg(feature = "xz2")]
+    Xz,
+
+    /// Multi-frame zstd.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Independently decompressible unit of work.
+enum Job {
+    #[cfg(feature = "flate2")]
+    Gzip(Vec<u8>),
+
+    #[cfg(feature = "xz2")]
+    Xz(Block, Vec<u8>),
+
+    #[cfg(feature = "zstd")]
+    Zstd(Vec<u8>),
+}
+
+impl Job {
+    /// Decompresses the unit, which must decompress to exactly `len` bytes,
+    /// so that it stays within the memory it was budgeted with.
+    fn decode(self, len: u64) -> io::Result<Vec<u8>> {
+        let mut decoded =
+            Vec::with_capacity(usize::try_from(len).unwrap_or_default());
+
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gzip(data) => {
+                flate2::bufread::GzDecoder::new(data.as_slice())
+                    .take(len + 1)
+                    .read_to_end(&mut decoded)?;
+            }
+
+            #[cfg(feature = "xz2")]
+            Self::Xz(block, data) => {
+                let (header, trailer) = seekable::xz_stream_frame(&block);
+                let stream = Cursor::new(header)
+                    .chain(data.as_slice())
+                    .chain(Cursor::new(trailer));
+                xz2::read::XzDecoder::new(stream)
+                    .take(len + 1)
+                    .read_to_end(&mut decoded)?;
+            }
+
+            #[cfg(feature = "zstd")]
+            Self::Zstd(data) => {
+                zstd::stream::read::Decoder::with_buffer(data.as_slice())?
+                    .take(len + 1)
+                    .read_to_end(&mut decoded)?;
+            }
+        }
+
+        if decoded.len() as u64 != len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "compressed unit does not match its uncompressed size",
+            ));
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Message from the dispatcher or a worker to the reader.
+enum Message {
+    /// Decompressed unit with its sequence number and memory cost.
+    Data(u64, u64, io::Result<Vec<u8>>),
+
+    /// Sequence number after the last unit.
+    End(u64),
+}
+
+/// Memory accounting of units that are in flight.
+struct Budget {
+    limit: u64,
+    used: Mutex<u64>,
+    released: Condvar,
+    closed: AtomicBool,
+}
+
+impl Budget {
+    /// Blocks until `cost` fits into the budget. Returns `false` if the
+    /// reader has been dropped.
+    fn acquire(&self, cost: u64) -> bool {
+        let Ok(mut used) = self.used.lock() else {
+            return false;
+        };
+
+        while *used > 0
+            && *used + cost > self.limit
+            && !self.closed.load(Ordering::Acquire)
+        {
+            used = match self.released.wait(used) {
+                Ok(used) => used,
+                Err(_) => return false,
+            };
+        }
+
+        *used += cost;
+
+        !self.closed.load(Ordering::Acquire)
+    }
+
+    fn release(&self, cost: u64) {
+        if let Ok(mut used) = self.used.lock() {
+            *used = used.saturating_sub(cost);
+        }
+
+        self.released.notify_all();
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.released.notify_all();
+    }
+}
+
+/// Unit sent to the workers: its sequence number, memory cost, uncompressed
+/// size and the unit itself.
+type Work = (u64, u64, u64, Job);
+
+/// Dispatcher state, running on its own thread.
+struct Dispatcher {
+    budget: Arc<Budget>,
+    jobs: SyncSender<Work>,
+    results: Sender<Message>,
+    seq: u64,
+}
+
+impl Dispatcher {
+    fn run(mut self, codec: Codec, file: Source) {
+        let result = match codec {
+            #[cfg(feature = "flate2")]
+            Codec::Gzip => self.gzip(file),
+
+            #[cfg(feature = "xz2")]
+            Codec::Xz => self.xz(file),
+
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => self.zstd(file),
+        };
+
+        let message = match result {
+            Ok(()) => Message::End(self.seq),
+            Err(e) => Message::Data(self.seq, 0, Err(e)),
+        };
+
+        // the reader may be gone already
+        let _ = self.results.send(message);
+    }
+
+    /// Sends a unit of `compressed` bytes that decompresses to `len` bytes
+    /// to the workers.
+    fn dispatch(
+        &mut self,
+        compressed: u64,
+        len: u64,
+        job: Job,
+    ) -> io::Result<()> {
+        let cost = compressed + len;
+
+        if !self.budget.acquire(cost) {
+            return Err(closed());
+        }
+
+        self.jobs
+            .send((self.seq, cost, len, job))
+            .map_err(|_| closed())?;
+        self.seq += 1;
+
+        Ok(())
+    }
+
+    /// Decompresses sequentially and sends the decompressed chunks directly
+    /// to the reader.
+    fn stream(&mut self, mut decoder: impl Read) -> io::Result<()> {
+        loop {
+            let mut chunk = vec![];
+            (&mut decoder).take(CHUNK_SIZE).read_to_end(&mut chunk)?;
+
+            if chunk.is_empty() {
+                return Ok(());
+            }
+
+            let cost = chunk.len() as u64;
+
+            if !self.budget.acquire(cost) {
+                return Err(closed());
+            }
+
+            self.results
+                .send(Message::Data(self.seq, cost, Ok(chunk)))
+                .map_err(|_| closed())?;
+            self.seq += 1;
+        }
+    }
+
+    #[cfg(feature = "flate2")]
+    fn gzip(&mut self, file: Source) -> io::Result<()> {
+        let mut reader = BufReader::with_capacity(1 << 16, file);
+
+        loop {
+            let buf = reader.fill_buf()?;
+
+            if buf.len() < 2 || buf[..2] != [0x1F, 0x8B] {
+                // end of file or trailing garbage, as ignored by gzip
+                return Ok(());
+            }
+
+            let Some(len) = bgzf_member_len(buf) else {
+                let decoder = flate2::bufread::GzDecoder::new(&mut reader);
+                self.stream(decoder)?;
+                continue;
+            };
+
+            let mut member = vec![0; usize::try_from(len).map_err(invalid)?];
+            reader.read_exact(&mut member)?;
+
+            // BGZF members are at most 64 KiB, so ISIZE does not wrap
+            let isize = member
+                .get(member.len().saturating_sub(4)..)
+                .map_or(0, |n| u32::from_le_bytes([n[0], n[1], n[2], n[3]]));
+            let isize = u64::from(isize);
+
+            if len + isize > self.budget.limit {
+                let member = member.as_slice();
+                self.stream(flate2::bufread::GzDecoder::new(member))?;
+            } else {
+                self.dispatch(len, isize, Job::Gzip(member))?;
+            }
+        }
+    }
+
+    #[cfg(feature = "xz2")]
+    fn xz(&mut self, mut file: Source) -> io::Result<()> {
+        let Some(blocks) = seekable::xz_blocks(&mut file)? else {
+            file.seek(SeekFrom::Start(0))?;
+            let file = BufReader::new(file);
+            return self.stream(xz2::read::XzDecoder::new_multi_decoder(file));
+        };
+
+        for block in blocks {
+            file.seek(SeekFrom::Start(block.compressed))?;
+            let mut region = (&mut file).take(block.compressed_len);
+
+            let cost = block.compressed_len + block.uncompressed_len;
+
+            if cost > self.budget.limit {
+                let (header, trailer) = seekable::xz_stream_frame(&block);
+                let stream = Cursor::new(header)
+                    .chain(region)
+                    .chain(Cursor::new(trailer));
+                self.stream(xz2::read::XzDecoder::new(stream))?;
+            } else {
+                let mut data = vec![];
+                region.read_to_end(&mut data)?;
+
+                let len = block.uncompressed_len;
+                self.dispatch(
+                    block.compressed_len,
+                    len,
+                    Job::Xz(block, data),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    fn zstd(&mut self, file: Source) -> io::Result<()> {
+        let mut reader = BufReader::with_capacity(1 << 16, file);
+
+        while !reader.fill_buf()?.is_empty() {
+            let mut frame = vec![0; 4];
+            reader.read_exact(&mut frame)?;
+            let magic =
+                u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]);
+
+            if magic & ZSTD_SKIPPABLE_MASK == ZSTD_SKIPPABLE_MAGIC {
+                let mut len = [0; 4];
+                reader.read_exact(&mut len)?;
+                let len = u64::from(u32::from_le_bytes(len));
+                io::copy(&mut (&mut reader).take(len), &mut io::sink())?;
+                continue;
+            }
+
+            if magic != ZSTD_MAGIC {
+                return Err(invalid("unknown zstd frame magic number"));
+            }
+
+            let content_size = zstd_frame_header(&mut reader, &mut frame)?;
+
+            // frames without a content size are streamed, as are frames
+            // whose compressed and uncompressed size exceed the limit
+            let limit = content_size
+                .and_then(|len| self.budget.limit.checked_sub(len));
+
+            let complete = match limit {
+                Some(limit) => {
+                    zstd_frame_blocks(&mut reader, &mut frame, limit)?
+                }
+                None => false,
+            };
+
+            if let (true, Some(len)) = (complete, content_size) {
+                let compressed = frame.len() as u64;
+                self.dispatch(compressed, len, Job::Zstd(frame))?;
+            } else {
+                let stream = Cursor::new(frame).chain(&mut reader);
+                let decoder =
+                    zstd::stream::read::Decoder::with_buffer(stream)?
+                        .single_frame();
+                self.stream(decoder)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the rest of a zstd frame header into `frame` and returns the frame
+/// content size, if known.
+#[cfg(feature = "zstd")]
+fn zstd_frame_header(
+    reader: &mut impl Read,
+    frame: &mut Vec<u8>,
+) -> io::Result<Option<u64>> {
+    let mut descriptor = [0];
+    reader.read_exact(&mut descriptor)?;
+    frame.push(descriptor[0]);
+
+    let fcs_flag = descriptor[0] >> 6;
+    let single_segment = descriptor[0] & 0x20 != 0;
+    let dict_id_len = [0, 1, 2, 4][usize::from(descriptor[0] & 0x03)];
+    let window_len = usize::from(!single_segment);
+    let fcs_len = match fcs_flag {
+        0 => usize::from(single_segment),
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+
+    let start = frame.len() + window_len + dict_id_len;
+    frame.resize(start + fcs_len, 0);
+    reader.read_exact(&mut frame[start - window_len - dict_id_len..])?;
+
+    let mut fcs = [0; 8];
+    fcs[..fcs_len].copy_from_slice(&frame[start..]);
+    let fcs = u64::from_le_bytes(fcs);
+
+    Ok(match fcs_len {
+        0 => None,
+        2 => Some(fcs + 256),
+        _ => Some(fcs),
+    })
+}
+
+/// Reads the blocks of a zstd frame into `frame`. Returns `false` as soon as
+/// the frame grows beyond `limit`, leaving the remaining blocks unread.
+#[cfg(feature = "zstd")]
+fn zstd_frame_blocks(
+    reader: &mut impl Read,
+    frame: &mut Vec<u8>,
+    limit: u64,
+) -> io::Result<bool> {
+    let has_checksum = frame[4] & 0x04 != 0;
+
+    loop {
+        if frame.len() as u64 > limit {
+            return Ok(false);
+        }
+
+        let mut header = [0; 3];
+        reader.read_exact(&mut header)?;
+        frame.extend_from_slice(&header);
+
+        let header = u32::from_le_bytes([header[0], header[1], header[2], 0]);
+        let last = header & 1 != 0;
+        let len = match (header >> 1) & 0x03 {
+            // raw and compressed blocks
+            0 | 2 => header >> 3,
+            // rle blocks
+            1 => 1,
+            _ => return Err(invalid("reserved zstd block type")),
+        };
+
+        let start = frame.len();
+        frame.resize(start + usize::try_from(len).map_err(invalid)?, 0);
+        reader.read_exact(&mut frame[start..])?;
+
+        if last {
+            break;
+        }
+    }
+
+    if has_checksum {
+        let start = frame.len();
+        frame.resize(start + 4, 0);
+        reader.read_exact(&mut frame[start..])?;
+    }
+
+    Ok(true)
+}
+
+/// Returns the length of a gzip member in the BGZF layout, which stores it
+/// in the `BC` extra subfield.
+#[cfg(feature = "flate2")]
+fn bgzf_member_len(header: &[u8]) -> Option<u64> {
+    const FEXTRA: u8 = 0x04;
+
+    if header.len() < 12 || header[3] & FEXTRA == 0 {
+        return None;
+    }
+
+    let xlen = usize::from(u16::from_le_bytes([header[10], header[11]]));
+    let mut extra = header.get(12..12 + xlen)?;
+
+    while extra.len() >= 4 {
+        let len = usize::from(u16::from_le_bytes([extra[2], extra[3]]));
+
+        if extra[..2] == *b"BC" && len == 2 {
+            let bsize = u16::from_le_bytes([*extra.get(4)?, *extra.get(5)?]);
+            return Some(u64::from(bsize) + 1);
+        }
+
+        extra = extra.get(4 + len..)?;
+    }
+
+    None
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "parallel reader was closed")
+}
+
+#[cfg(any(feature = "flate2", feature = "zstd"))]
+fn invalid(
+    e: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Reader that decompresses independent units in parallel and returns the
+/// decompressed stream in order.
+pub struct Parallel {
+    budget: Arc<Budget>,
+    results: Receiver<Message>,
+    pending: BTreeMap<u64, (u64, io::Result<Vec<u8>>)>,
+    next: u64,
+    end: Option<u64>,
+    current: Option<(u64, Cursor<Vec<u8>>)>,
+}
+
+impl Parallel {
+    /// Returns a reader decompressing `file` with `threads` worker threads,
+    /// keeping at most roughly `memory_limit` bytes of compressed and
+    /// decompressed units in flight.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if spawning the threads fails.
+    pub fn new(
+        file: Source,
+        codec: Codec,
+        threads: usize,
+        memory_limit: u64,
+    ) -> io::Result<Self> {
+        let budget = Arc::new(Budget {
+            limit: memory_limit,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+            closed: AtomicBool::new(false),
+        });
+
+        let (results_tx, results) = mpsc::channel();
+        let (jobs_tx, jobs) = mpsc::sync_channel::<Work>(threads);
+        let jobs = Arc::new(Mutex::new(jobs));
+
+        for i in 0..threads.max(1) {
+            let jobs = Arc::clone(&jobs);
+            let results = results_tx.clone();
+
+            thread::Builder::new()
+                .name(format!("archive-rs-decoder-{i}"))
+                .spawn(move || loop {
+                    let job = match jobs.lock() {
+                        Ok(jobs) => jobs.recv(),
+                        Err(_) => return,
+                    };
+
+                    let Ok((seq, cost, len, job)) = job else {
+                        return;
+                    };
+
+                    let message = Message::Data(seq, cost, job.decode(len));
+
+                    if results.send(message).is_err() {
+                        return;
+                    }
+                })?;
+        }
+
+        let dispatcher = Dispatcher {
+            budget: Arc::clone(&budget),
+            jobs: jobs_tx,
+            results: results_tx,
+            seq: 0,
+        };
+
+        thread::Builder::new()
+            .name(String::from("archive-rs-dispatcher"))
+            .spawn(move || dispatcher.run(codec, file))?;
+
+        Ok(Self {
+            budget,
+            results,
+            pending: BTreeMap::new(),
+            next: 0,
+            end: None,
+            current: None,
+        })
+    }
+
+    /// Returns the next decompressed unit in order, or `None` at the end.
+    fn next_unit(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        loop {
+            if let Some((cost, data)) = self.pending.remove(&self.next) {
+                self.next += 1;
+
+                if data.is_err() {
+                    self.budget.release(cost);
+                }
+
+                return data.map(|data| Some((cost, data)));
+            }
+
+            if self.end == Some(self.next) {
+                return Ok(None);
+            }
+
+            match self.results.recv() {
+                Ok(Message::Data(seq, cost, data)) => {
+                    self.pending.insert(seq, (cost, data));
+                }
+
+                Ok(Message::End(seq)) => self.end = Some(seq),
+
+                Err(_) => {
+                    return Err(io::Error::other(
+                        "decompression thread terminated unexpectedly",
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl Read for Parallel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some((cost, current)) = &mut self.current {
+                let n = current.read(buf)?;
+
+                if n > 0 || buf.is_empty() {
+                    return Ok(n);
+                }
+
+                self.budget.release(*cost);
+                self.current = None;
+            }
+
+            match self.next_unit()? {
+                Some((cost, data)) => {
+                    self.current = Some((cost, Cursor::new(data)));
+                }
+
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+impl Drop for Parallel {
+    fn drop(&mut self) {
+        self.budget.close();
+    }
+}
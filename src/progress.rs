@@ -0,0 +1,73 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Progress of iterating the entries of an archive.
+///
+/// See [`Archive::set_progress_observer`](crate::Archive::set_progress_observer).
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct Progress<'a> {
+    /// Number of bytes of the archive file that have been read.
+    pub compressed: u64,
+
+    /// Size of the archive file in bytes.
+    pub compressed_total: u64,
+
+    /// Number of bytes of the uncompressed archive that have been read,
+    /// including the headers of the current entry but not its data.
+    pub uncompressed: u64,
+
+    /// Number of entries so far, including the current one.
+    pub entries: u64,
+
+    /// Path of the current entry, if it can be parsed.
+    pub path: Option<&'a Path>,
+}
+
+/// Token to cancel processing an archive, e.g. from another thread.
+///
+/// Once cancelled, reading the archive fails and iterating its entries
+/// returns [`Error::Cancelled`](crate::Error::Cancelled).
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::{CancellationToken, OpenOptions};
+///
+/// let token = CancellationToken::new();
+///
+/// let mut archive = OpenOptions::new()
+///     .cancellation_token(token.clone())
+///     .open("backup.tar.gz")?;
+///
+/// // e.g. from the UI thread
+/// token.cancel();
+///
+/// assert!(matches!(
+///     archive.entries()?.next(),
+///     Some(Err(archive_rs::Error::Cancelled))
+/// ));
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Returns a new token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels processing of all archives using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Returns true if this token has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
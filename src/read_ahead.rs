@@ -0,0 +1,89 @@
+//! Read-ahead pipeline that decompresses on a dedicated thread into a bounded
+//! ring of buffers, so that decompression overlaps with the processing of the
+//! entries by the caller.
+
+use std::io::{self, Cursor, Read};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+
+/// Size of each buffer of the ring.
+pub const BUFFER_SIZE: u64 = 256 << 10;
+
+/// Reader returning the buffers filled by the read-ahead thread.
+pub struct ReadAhead {
+    filled: Receiver<io::Result<Vec<u8>>>,
+    free: SyncSender<Vec<u8>>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl ReadAhead {
+    /// Returns a reader that reads `reader` ahead on a dedicated thread into
+    /// a ring of `buffers` buffers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if spawning the thread fails.
+    pub fn new(
+        mut reader: impl Read + Send + 'static,
+        buffers: usize,
+    ) -> io::Result<Self> {
+        let buffers = buffers.max(1);
+
+        let (filled_tx, filled) = mpsc::sync_channel(buffers);
+        let (free, free_rx) = mpsc::sync_channel::<Vec<u8>>(buffers);
+
+        for _ in 0..buffers {
+            free.send(vec![]).map_err(io::Error::other)?;
+        }
+
+        thread::Builder::new()
+            .name(String::from("archive-rs-read-ahead"))
+            .spawn(move || {
+                while let Ok(mut buf) = free_rx.recv() {
+                    buf.clear();
+
+                    let result = (&mut reader)
+                        .take(BUFFER_SIZE)
+                        .read_to_end(&mut buf)
+                        .map(|_| buf);
+
+                    let done = !matches!(&result, Ok(buf) if !buf.is_empty());
+
+                    // stop at the end of the stream, on errors and as soon as
+                    // the reader has been dropped
+                    if filled_tx.send(result).is_err() || done {
+                        return;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            filled,
+            free,
+            current: Cursor::new(vec![]),
+        })
+    }
+}
+
+impl Read for ReadAhead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+
+            let next = match self.filled.recv() {
+                Ok(Ok(next)) if !next.is_empty() => next,
+                Ok(Err(e)) => return Err(e),
+                Ok(Ok(_)) | Err(_) => return Ok(0),
+            };
+
+            let used = std::mem::replace(&mut self.current, Cursor::new(next));
+
+            // the thread may have stopped already
+            let _ = self.free.send(used.into_inner());
+        }
+    }
+}
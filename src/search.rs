@@ -0,0 +1,210 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+
+use regex::bytes::Regex;
+
+use crate::{Entries, Entry, Result};
+
+/// Maximum length of lines, of which longer lines are only matched up to it.
+const MAX_LINE: u64 = 1 << 20;
+
+/// Line of an entry that matches, see [`Entries::search`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Hit {
+    /// Path of the entry.
+    pub path: PathBuf,
+
+    /// Number of the line, starting at 1.
+    pub line_number: u64,
+
+    /// Line without its line break, invalid UTF-8 replaced, or empty if the
+    /// entry is binary.
+    pub line: String,
+
+    /// Whether the entry is binary, i.e. contains a NUL byte in its first
+    /// block. Like `grep`, binary entries yield only their first hit, after
+    /// which the rest of the entry is skipped.
+    pub binary: bool,
+}
+
+/// Iterator over the lines of the regular files of an archive that match a
+/// regular expression, see [`Entries::search`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct Search<'a> {
+    entries: Entries<'a>,
+    regex: Regex,
+    current: Option<Current<'a>>,
+    line: Vec<u8>,
+}
+
+/// Entry that is being searched.
+struct Current<'a> {
+    path: PathBuf,
+    reader: BufReader<Entry<'a>>,
+    line_number: u64,
+    binary: bool,
+}
+
+impl<'a> Entries<'a> {
+    /// Returns the lines of the regular files of these entries that match
+    /// `regex`, like `zgrep`.
+    ///
+    /// Only the first MiB of longer lines is matched, so that binary entries
+    /// without line breaks are not read into memory at once.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use archive_rs::Archive;
+    /// use regex::bytes::Regex;
+    ///
+    /// let regex = Regex::new("ERROR|FATAL")?;
+    /// let mut archive = Archive::open("logs.tar.zst")?;
+    ///
+    /// for hit in archive.entries()?.search(&regex) {
+    ///     let hit = hit?;
+    ///     println!("{}:{}:{}", hit.path.display(), hit.line_number, hit.line);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn search(self, regex: &Regex) -> Search<'a> {
+        Search {
+            entries: self,
+            regex: regex.clone(),
+            current: None,
+            line: vec![],
+        }
+    }
+}
+
+impl Search<'_> {
+    /// Skips the remaining lines of the current entry, e.g. after its first
+    /// hit if only the paths of matching entries are needed.
+    pub fn skip_entry(&mut self) {
+        self.current = None;
+    }
+}
+
+impl Iterator for Search<'_> {
+    type Item = Result<Hit>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(current) = &mut self.current else {
+                match self.entries.next()? {
+                    Ok(entry) if entry.entry_type().is_file_or_sparse() => {
+                        match Current::new(entry) {
+                            Ok(current) => self.current = Some(current),
+                            Err(e) => return Some(Err(e)),
+                        }
+                    }
+
+                    Ok(_) => {}
+                    Err(e) => return Some(Err(e)),
+                }
+
+                continue;
+            };
+
+            self.line.clear();
+
+            let mut reader = (&mut current.reader).take(MAX_LINE);
+
+            let read =
+                reader.read_until(b'\n', &mut self.line).and_then(|n| {
+                    // skips the rest of a line that is too long
+                    if n as u64 == MAX_LINE && !self.line.ends_with(b"\n") {
+                        current.reader.skip_until(b'\n')?;
+                    }
+
+                    Ok(n)
+                });
+
+            match read {
+                Ok(0) => {
+                    self.current = None;
+                    continue;
+                }
+
+                Ok(_) => current.line_number += 1,
+
+                Err(e) => {
+                    self.current = None;
+                    return Some(Err(e.into()));
+                }
+            }
+
+            let line = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+            if !self.regex.is_match(line) {
+                continue;
+            }
+
+            let hit = Hit {
+                path: current.path.clone(),
+                line_number: current.line_number,
+                line: if current.binary {
+                    String::new()
+                } else {
+                    String::from_utf8_lossy(line).into_owned()
+                },
+                binary: current.binary,
+            };
+
+            if current.binary {
+                self.current = None;
+            }
+
+            return Some(Ok(hit));
+        }
+    }
+}
+
+impl<'a> Current<'a> {
+    fn new(entry: Entry<'a>) -> Result<Self> {
+        let path = entry.path()?.into_owned();
+        let mut reader = BufReader::new(entry);
+        let binary = reader.fill_buf()?.contains(&0);
+
+        Ok(Self {
+            path,
+            reader,
+            line_number: 0,
+            binary,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn searches_sparse_files() {
+        let mut text = vec![b'-'; 505];
+        text.extend_from_slice(b"\nneedle");
+
+        let tarball = testing::tarball(|builder| {
+            testing::sparse(builder, "sparse", 512, &[(0, &text), (512, b"")]);
+        });
+
+        let (_file, mut archive) = testing::open(&tarball, ".tar");
+        let regex = Regex::new("ne+dle$").unwrap();
+
+        let hits: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .search(&regex)
+            .map(|hit| {
+                let hit = hit.unwrap();
+                (hit.path, hit.line_number, hit.line)
+            })
+            .collect();
+
+        let sparse = (PathBuf::from("sparse"), 2, "needle".to_owned());
+        assert_eq!(hits, [sparse]);
+    }
+}
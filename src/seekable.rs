@@ -0,0 +1,804 @@
+//! Random access into compressed tarballs that consist of independently
+//! compressed blocks, i.e. zstd files in the [seekable format] and xz files
+//! with multiple blocks, as produced by `xz -T`.
+//!
+//! [seekable format]: https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md
+
+#[cfg(feature = "xz2")]
+use std::io::Cursor;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+use tar::Archive as Tar;
+
+use crate::source::{Source, State, Tracked};
+
+#[cfg(feature = "xz2")]
+const XZ_HEADER_MAGIC: [u8; 6] = [0xFD, b'7', b'z', b'X', b'Z', 0x00];
+#[cfg(feature = "xz2")]
+const XZ_FOOTER_MAGIC: [u8; 2] = [b'Y', b'Z'];
+#[cfg(feature = "xz2")]
+const XZ_HEADER_SIZE: u64 = 12;
+#[cfg(feature = "xz2")]
+const XZ_FOOTER_SIZE: u64 = 12;
+
+#[cfg(feature = "zstd")]
+const ZSTD_SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+#[cfg(feature = "zstd")]
+const ZSTD_SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+#[cfg(feature = "zstd")]
+const ZSTD_SEEKABLE_FOOTER_SIZE: u64 = 9;
+#[cfg(feature = "zstd")]
+const ZSTD_SKIPPABLE_HEADER_SIZE: u64 = 8;
+
+/// Compression codec of the blocks.
+#[derive(Clone, Copy, Debug)]
+pub enum Codec {
+    /// Every block is an xz block that needs to be wrapped in a stream.
+    #[cfg(feature = "xz2")]
+    Xz,
+
+    /// Every block is a complete zstd frame.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Independently decompressible block.
+#[derive(Clone, Copy, Debug)]
+pub struct Block {
+    pub compressed: u64,
+    pub compressed_len: u64,
+    pub uncompressed: u64,
+    pub uncompressed_len: u64,
+
+    /// xz only: unpadded size and stream flags of the containing stream.
+    #[cfg(feature = "xz2")]
+    unpadded_len: u64,
+    #[cfg(feature = "xz2")]
+    flags: [u8; 2],
+}
+
+impl Block {
+    const fn uncompressed_end(&self) -> u64 {
+        self.uncompressed + self.uncompressed_len
+    }
+}
+
+/// Shared, immutable state of all readers of one file.
+#[derive(Debug)]
+struct Shared {
+    file: Mutex<Source>,
+    codec: Codec,
+    blocks: Vec<Block>,
+    len: u64,
+}
+
+/// Reader over the uncompressed stream that only decompresses the blocks it
+/// reads from.
+///
+/// Positions are relative to the base the reader was created with, see
+/// [`Seekable::at`].
+pub struct Seekable {
+    shared: Arc<Shared>,
+    base: u64,
+    pos: u64,
+    current: Option<Current>,
+}
+
+/// Currently open block.
+struct Current {
+    block: usize,
+    pos: u64,
+    decoder: Box<dyn Read + Send>,
+}
+
+impl Seekable {
+    /// Returns a seekable reader for an xz file, if it has more than one
+    /// block. Otherwise, the file is rewound and returned, so it can be
+    /// streamed instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the file fails.
+    #[cfg(feature = "xz2")]
+    pub fn xz(mut file: Source) -> io::Result<Result<Self, Source>> {
+        match xz_blocks(&mut file)? {
+            Some(blocks) if blocks.len() > 1 => {
+                Ok(Ok(Self::new(file, Codec::Xz, blocks)))
+            }
+            _ => {
+                file.rewind()?;
+                Ok(Err(file))
+            }
+        }
+    }
+
+    /// Returns a seekable reader for a zstd file, if it carries a seek table
+    /// with more than one frame. Otherwise, the file is rewound and
+    /// returned, so it can be streamed instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the file fails.
+    #[cfg(feature = "zstd")]
+    pub fn zstd(mut file: Source) -> io::Result<Result<Self, Source>> {
+        match zstd_blocks(&mut file)? {
+            Some(blocks) if blocks.len() > 1 => {
+                Ok(Ok(Self::new(file, Codec::Zstd, blocks)))
+            }
+            _ => {
+                file.rewind()?;
+                Ok(Err(file))
+            }
+        }
+    }
+
+    fn new(file: Source, codec: Codec, blocks: Vec<Block>) -> Self {
+        let len = blocks.last().map_or(0, Block::uncompressed_end);
+
+        let shared = Shared {
+            file: Mutex::new(file),
+            codec,
+            blocks,
+            len,
+        };
+
+        Self {
+            shared: Arc::new(shared),
+            base: 0,
+            pos: 0,
+            current: None,
+        }
+    }
+
+    /// Returns a new reader over the same file, starting at `offset` of the
+    /// uncompressed stream. Positions of the new reader are relative to
+    /// `offset`.
+    pub fn at(&self, offset: u64) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            base: offset,
+            pos: offset,
+            current: None,
+        }
+    }
+
+    // ALLOW only the zstd decoder fails to read the frame header
+    #[allow(clippy::unnecessary_wraps)]
+    fn open(&self, index: usize) -> io::Result<Current> {
+        let block = self.shared.blocks[index];
+
+        let region = Region {
+            shared: Arc::clone(&self.shared),
+            pos: block.compressed,
+            end: block.compressed + block.compressed_len,
+        };
+
+        let decoder: Box<dyn Read + Send> = match self.shared.codec {
+            #[cfg(feature = "xz2")]
+            Codec::Xz => {
+                let (header, footer) = xz_stream_frame(&block);
+                let stream = Cursor::new(header)
+                    .chain(region)
+                    .chain(Cursor::new(footer));
+                Box::new(xz2::read::XzDecoder::new(stream))
+            }
+
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Box::new(zstd::stream::read::Decoder::new(region)?),
+        };
+
+        Ok(Current {
+            block: index,
+            pos: block.uncompressed,
+            decoder,
+        })
+    }
+}
+
+impl Read for Seekable {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.shared.len {
+            return Ok(0);
+        }
+
+        let index = self
+            .shared
+            .blocks
+            .partition_point(|block| block.uncompressed_end() <= self.pos);
+
+        let reusable = matches!(
+            &self.current,
+            Some(current) if current.block == index && current.pos <= self.pos
+        );
+
+        if !reusable {
+            self.current = Some(self.open(index)?);
+        }
+
+        let block = self.shared.blocks[index];
+        let current = self.current.as_mut().expect("block has been opened");
+
+        let skip = self.pos - current.pos;
+        if skip > 0 {
+            let skipped = io::copy(
+                &mut (&mut current.decoder).take(skip),
+                &mut io::sink(),
+            )?;
+            current.pos += skipped;
+
+            if skipped < skip {
+                return Err(truncated_block());
+            }
+        }
+
+        let max = usize::try_from(block.uncompressed_end() - self.pos)
+            .unwrap_or(usize::MAX)
+            .min(buf.len());
+
+        let n = current.decoder.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(truncated_block());
+        }
+
+        current.pos += n as u64;
+        self.pos += n as u64;
+
+        if self.pos == block.uncompressed_end() {
+            // read to the end of the block, which verifies its check
+            if current.decoder.read(&mut [0])? != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "compressed block exceeds its uncompressed size",
+                ));
+            }
+
+            self.current = None;
+        }
+
+        Ok(n)
+    }
+}
+
+impl Seek for Seekable {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(self.base + n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+            SeekFrom::End(n) => self.shared.len.checked_add_signed(n),
+        };
+
+        match pos {
+            Some(pos) if pos >= self.base => {
+                self.pos = pos;
+                Ok(pos - self.base)
+            }
+
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Tarball over a [`Seekable`] reader that can be repositioned to any entry.
+pub struct SeekableTar {
+    tar: Tar<Tracked<Seekable>>,
+    origin: Seekable,
+    state: Arc<State>,
+}
+
+impl SeekableTar {
+    /// Returns a tarball reading from the start of `reader`, which tracks its
+    /// position relative to the current base in `state`.
+    pub fn new(reader: Seekable, state: &Arc<State>) -> Self {
+        let origin = reader.at(0);
+
+        Self {
+            tar: Tar::new(Tracked::new(reader, state)),
+            origin,
+            state: Arc::clone(state),
+        }
+    }
+
+    /// Returns the entries and the uncompressed offset they are relative to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the archive fails.
+    pub fn entries(
+        &mut self,
+    ) -> io::Result<(tar::Entries<'_, Tracked<Seekable>>, u64)> {
+        let base = self.origin.base;
+        let entries = self.tar.entries_with_seek()?;
+        Ok((entries, base))
+    }
+
+    /// Returns the reader, positioned after the entries that have been read.
+    pub fn into_inner(self) -> Seekable {
+        self.tar.into_inner().into_inner()
+    }
+
+    /// Repositions the tarball to read the entries starting at `offset` of
+    /// the uncompressed stream.
+    pub fn seek_to(&mut self, offset: u64) {
+        self.origin = self.origin.at(offset);
+        self.tar = Tar::new(Tracked::new(self.origin.at(offset), &self.state));
+    }
+}
+
+/// Reader of a region of the shared file.
+struct Region {
+    shared: Arc<Shared>,
+    pos: u64,
+    end: u64,
+}
+
+impl Read for Region {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = usize::try_from(self.end - self.pos)
+            .unwrap_or(usize::MAX)
+            .min(buf.len());
+
+        if max == 0 {
+            return Ok(0);
+        }
+
+        let mut file =
+            self.shared.file.lock().map_err(|_| {
+                io::Error::other("archive file lock is poisoned")
+            })?;
+
+        file.seek(SeekFrom::Start(self.pos))?;
+        let n = file.read(&mut buf[..max])?;
+        drop(file);
+
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+fn truncated_block() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "compressed block ended before its uncompressed size",
+    )
+}
+
+fn read_at<const N: usize>(
+    file: &mut Source,
+    pos: u64,
+) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    file.seek(SeekFrom::Start(pos))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Returns the blocks of all streams of an xz file by reading the stream
+/// indexes from the end of the file, or `None` if the file is not a valid xz
+/// file.
+#[cfg(feature = "xz2")]
+pub fn xz_blocks(file: &mut Source) -> io::Result<Option<Vec<Block>>> {
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let mut streams = vec![];
+
+    while end > 0 {
+        if end < XZ_HEADER_SIZE + XZ_FOOTER_SIZE || end % 4 != 0 {
+            return Ok(None);
+        }
+
+        // stream padding
+        if read_at::<4>(file, end - 4)? == [0; 4] {
+            end -= 4;
+            continue;
+        }
+
+        let footer = read_at::<12>(file, end - XZ_FOOTER_SIZE)?;
+        if footer[10..12] != XZ_FOOTER_MAGIC {
+            return Ok(None);
+        }
+
+        let index_len = (u64::from(u32_le(&footer[4..8])) + 1) * 4;
+        let flags = [footer[8], footer[9]];
+
+        let Some(index_start) = (end - XZ_FOOTER_SIZE).checked_sub(index_len)
+        else {
+            return Ok(None);
+        };
+
+        let mut index = vec![0; usize::try_from(index_len).unwrap_or(0)];
+        file.seek(SeekFrom::Start(index_start))?;
+        file.read_exact(&mut index)?;
+
+        let Some(records) = xz_index_records(&index) else {
+            return Ok(None);
+        };
+
+        let blocks_len: u64 = records
+            .iter()
+            .map(|(unpadded, _)| unpadded.next_multiple_of(4))
+            .sum();
+
+        let Some(stream_start) = index_start
+            .checked_sub(blocks_len)
+            .and_then(|n| n.checked_sub(XZ_HEADER_SIZE))
+        else {
+            return Ok(None);
+        };
+
+        let header = read_at::<12>(file, stream_start)?;
+        if header[..6] != XZ_HEADER_MAGIC || header[6..8] != flags {
+            return Ok(None);
+        }
+
+        let mut compressed = stream_start + XZ_HEADER_SIZE;
+        let mut blocks = vec![];
+
+        for (unpadded_len, uncompressed_len) in records {
+            let compressed_len = unpadded_len.next_multiple_of(4);
+
+            blocks.push(Block {
+                compressed,
+                compressed_len,
+                uncompressed: 0,
+                uncompressed_len,
+                unpadded_len,
+                flags,
+            });
+
+            compressed += compressed_len;
+        }
+
+        streams.push(blocks);
+        end = stream_start;
+    }
+
+    let mut uncompressed = 0;
+    let mut blocks: Vec<Block> = streams.into_iter().rev().flatten().collect();
+
+    for block in &mut blocks {
+        block.uncompressed = uncompressed;
+        uncompressed += block.uncompressed_len;
+    }
+
+    Ok(Some(blocks))
+}
+
+/// Returns the `(unpadded size, uncompressed size)` records of an xz index.
+#[cfg(feature = "xz2")]
+fn xz_index_records(index: &[u8]) -> Option<Vec<(u64, u64)>> {
+    let (&indicator, mut rest) = index.split_first()?;
+    if indicator != 0 {
+        return None;
+    }
+
+    let count = varint_decode(&mut rest)?;
+    let mut records = vec![];
+
+    for _ in 0..count {
+        let unpadded = varint_decode(&mut rest)?;
+        let uncompressed = varint_decode(&mut rest)?;
+        records.push((unpadded, uncompressed));
+    }
+
+    Some(records)
+}
+
+/// Returns the bytes that turn a single xz block into a complete stream: the
+/// stream header in front and the index with the stream footer after it.
+#[cfg(feature = "xz2")]
+pub fn xz_stream_frame(block: &Block) -> (Vec<u8>, Vec<u8>) {
+    let mut header = XZ_HEADER_MAGIC.to_vec();
+    header.extend_from_slice(&block.flags);
+    header.extend_from_slice(&crc32(&block.flags).to_le_bytes());
+
+    let mut index = vec![0];
+    varint_encode(&mut index, 1);
+    varint_encode(&mut index, block.unpadded_len);
+    varint_encode(&mut index, block.uncompressed_len);
+    index.resize(index.len().next_multiple_of(4), 0);
+    index.extend_from_slice(&crc32(&index).to_le_bytes());
+
+    let backward_size = u32::try_from(index.len() / 4 - 1)
+        .expect("single record index is small");
+
+    let mut footer = backward_size.to_le_bytes().to_vec();
+    footer.extend_from_slice(&block.flags);
+    let crc = crc32(&footer);
+
+    let mut trailer = index;
+    trailer.extend_from_slice(&crc.to_le_bytes());
+    trailer.extend_from_slice(&footer);
+    trailer.extend_from_slice(&XZ_FOOTER_MAGIC);
+
+    (header, trailer)
+}
+
+#[cfg(feature = "xz2")]
+fn varint_decode(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+
+    for i in 0..9 {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+
+        value |= u64::from(byte & 0x7F) << (i * 7);
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+#[cfg(feature = "xz2")]
+fn varint_encode(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        // ALLOW truncation is intended, only the lowest 7 bits are used
+        #[allow(clippy::cast_possible_truncation)]
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    // ALLOW value is less than 0x80
+    #[allow(clippy::cast_possible_truncation)]
+    bytes.push(value as u8);
+}
+
+#[cfg(feature = "xz2")]
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// Returns the frames of a zstd file from its seek table, or `None` if the
+/// file does not end with a seek table.
+#[cfg(feature = "zstd")]
+fn zstd_blocks(file: &mut Source) -> io::Result<Option<Vec<Block>>> {
+    let len = file.seek(SeekFrom::End(0))?;
+
+    if len < ZSTD_SEEKABLE_FOOTER_SIZE + ZSTD_SKIPPABLE_HEADER_SIZE {
+        return Ok(None);
+    }
+
+    let footer = read_at::<9>(file, len - ZSTD_SEEKABLE_FOOTER_SIZE)?;
+    if u32_le(&footer[5..9]) != ZSTD_SEEKABLE_MAGIC || footer[4] & 0x7C != 0 {
+        return Ok(None);
+    }
+
+    let frames = u64::from(u32_le(&footer[0..4]));
+    let entry_len = if footer[4] & 0x80 == 0 { 8 } else { 12 };
+    let table_len = frames * entry_len;
+
+    let Some(table_start) = len.checked_sub(
+        ZSTD_SEEKABLE_FOOTER_SIZE + ZSTD_SKIPPABLE_HEADER_SIZE + table_len,
+    ) else {
+        return Ok(None);
+    };
+
+    let header = read_at::<8>(file, table_start)?;
+    if u32_le(&header[0..4]) != ZSTD_SKIPPABLE_MAGIC
+        || u64::from(u32_le(&header[4..8]))
+            != table_len + ZSTD_SEEKABLE_FOOTER_SIZE
+    {
+        return Ok(None);
+    }
+
+    let mut table = vec![0; usize::try_from(table_len).unwrap_or(0)];
+    file.read_exact(&mut table)?;
+
+    let mut compressed = 0;
+    let mut uncompressed = 0;
+    let mut blocks = vec![];
+
+    for entry in table.chunks_exact(usize::try_from(entry_len).unwrap_or(8)) {
+        let compressed_len = u64::from(u32_le(&entry[0..4]));
+        let uncompressed_len = u64::from(u32_le(&entry[4..8]));
+
+        blocks.push(Block {
+            compressed,
+            compressed_len,
+            uncompressed,
+            uncompressed_len,
+            #[cfg(feature = "xz2")]
+            unpadded_len: compressed_len,
+            #[cfg(feature = "xz2")]
+            flags: [0; 2],
+        });
+
+        compressed += compressed_len;
+        uncompressed += uncompressed_len;
+    }
+
+    if compressed != table_start {
+        return Ok(None);
+    }
+
+    Ok(Some(blocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::source::State;
+
+    /// Writes `bytes` to a temporary file and opens it.
+    fn open(bytes: &[u8]) -> Source {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(bytes).unwrap();
+
+        let path = file.path().to_owned();
+        Source::open(&[path], &State::new(None)).unwrap()
+    }
+
+    fn data() -> Vec<u8> {
+        (0..100_000_u32).flat_map(u32::to_le_bytes).collect()
+    }
+
+    fn read_from(seekable: &Seekable, offset: usize) -> Vec<u8> {
+        let mut buf = vec![];
+        let offset = u64::try_from(offset).unwrap();
+        seekable.at(offset).read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    #[cfg(feature = "xz2")]
+    #[test]
+    fn crc32_of_check_string() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[cfg(feature = "xz2")]
+    #[test]
+    fn varint_round_trip() {
+        for value in [0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, u64::MAX >> 1] {
+            let mut bytes = vec![];
+            varint_encode(&mut bytes, value);
+
+            let mut rest = bytes.as_slice();
+            assert_eq!(varint_decode(&mut rest), Some(value));
+            assert!(rest.is_empty());
+        }
+
+        assert_eq!(varint_decode(&mut [0x80, 0x01].as_slice()), Some(0x80));
+        assert_eq!(varint_decode(&mut [0x80].as_slice()), None);
+        assert_eq!(varint_decode(&mut [0xFF; 10].as_slice()), None);
+    }
+
+    #[cfg(feature = "xz2")]
+    #[test]
+    fn xz_index() {
+        let index = [0x00, 0x02, 0x10, 0x80, 0x01, 0x20, 0x05];
+        assert_eq!(xz_index_records(&index), Some(vec![(16, 128), (32, 5)]));
+
+        assert_eq!(xz_index_records(&[0x01, 0x00]), None);
+        assert_eq!(xz_index_records(&[0x00, 0x02, 0x10, 0x80]), None);
+    }
+
+    #[cfg(feature = "xz2")]
+    #[test]
+    fn xz_streams() {
+        let data = data();
+        let mut file = vec![];
+
+        for part in [&data[..150_000], &data[150_000..]] {
+            let mut encoder = xz2::write::XzEncoder::new(vec![], 6);
+            encoder.write_all(part).unwrap();
+            file.extend(encoder.finish().unwrap());
+            file.extend([0; 4]);
+        }
+
+        let mut source = open(&file);
+        let blocks = xz_blocks(&mut source).unwrap().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].uncompressed, 150_000);
+        assert_eq!(blocks[1].uncompressed_end(), 400_000);
+
+        let Ok(seekable) = Seekable::xz(source).unwrap() else {
+            panic!("xz file is not seekable");
+        };
+        assert_eq!(read_from(&seekable, 0), data);
+        assert_eq!(read_from(&seekable, 149_999), &data[149_999..]);
+        assert_eq!(read_from(&seekable, 300_000), &data[300_000..]);
+    }
+
+    #[cfg(feature = "xz2")]
+    #[test]
+    fn xz_invalid() {
+        let mut source = open(&[0; 64]);
+        assert!(xz_blocks(&mut source).unwrap().is_none());
+    }
+
+    /// Returns `data` compressed as zstd frames of `frame` bytes with a
+    /// seek table.
+    #[cfg(feature = "zstd")]
+    fn zstd_seekable(data: &[u8], frame: usize) -> Vec<u8> {
+        let mut file = vec![];
+        let mut table = vec![];
+
+        for chunk in data.chunks(frame) {
+            let compressed = zstd::encode_all(chunk, 3).unwrap();
+            let compressed_len = u32::try_from(compressed.len()).unwrap();
+            let len = u32::try_from(chunk.len()).unwrap();
+
+            table.extend(compressed_len.to_le_bytes());
+            table.extend(len.to_le_bytes());
+            file.extend(compressed);
+        }
+
+        let frames = u32::try_from(data.len().div_ceil(frame)).unwrap();
+        let size = u32::try_from(table.len()).unwrap() + 9;
+
+        file.extend(ZSTD_SKIPPABLE_MAGIC.to_le_bytes());
+        file.extend(size.to_le_bytes());
+        file.extend(table);
+        file.extend(frames.to_le_bytes());
+        file.push(0);
+        file.extend(ZSTD_SEEKABLE_MAGIC.to_le_bytes());
+        file
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_seek_table() {
+        let data = data();
+        let mut source = open(&zstd_seekable(&data, 65_536));
+
+        let blocks = zstd_blocks(&mut source).unwrap().unwrap();
+        assert_eq!(blocks.len(), 7);
+        assert_eq!(blocks[1].uncompressed, 65_536);
+        assert_eq!(blocks[6].uncompressed_end(), 400_000);
+        assert_eq!(blocks[1].compressed, blocks[0].compressed_len);
+
+        let Ok(seekable) = Seekable::zstd(source).unwrap() else {
+            panic!("zstd file is not seekable");
+        };
+        assert_eq!(read_from(&seekable, 0), data);
+        assert_eq!(read_from(&seekable, 65_535), &data[65_535..]);
+        assert_eq!(read_from(&seekable, 393_216), &data[393_216..]);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_invalid_seek_table() {
+        let data = data();
+        let mut file = zstd_seekable(&data, 65_536);
+
+        // first frame has the wrong size
+        let table = file.len() - 9 - 7 * 8;
+        file[table] ^= 1;
+
+        let mut source = open(&file);
+        assert!(zstd_blocks(&mut source).unwrap().is_none());
+
+        let plain = zstd::encode_all(data.as_slice(), 3).unwrap();
+        let mut source = open(&plain);
+        assert!(zstd_blocks(&mut source).unwrap().is_none());
+    }
+}
@@ -0,0 +1,387 @@
+//! Archive file reader that tracks its position for progress reporting and
+//! that checks for cancellation before every read, and reader of the
+//! uncompressed tarball that tracks its position and records entry headers.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::CancellationToken;
+
+/// State shared between the archive and its file reader, which may be used
+/// on another thread.
+#[derive(Debug, Default)]
+pub struct State {
+    position: AtomicU64,
+    len: AtomicU64,
+    cancellation: CancellationToken,
+    tarball: Mutex<Tarball>,
+}
+
+/// Position of the [`Tracked`] reader of the uncompressed tarball.
+#[derive(Debug, Default)]
+struct Tarball {
+    position: u64,
+
+    /// Position from which on everything read is recorded, and the bytes
+    /// recorded so far.
+    recording: Option<(u64, Vec<u8>)>,
+}
+
+impl State {
+    /// Returns the state of a reader that is cancelled via `cancellation`.
+    pub fn new(cancellation: Option<CancellationToken>) -> Arc<Self> {
+        Arc::new(Self {
+            cancellation: cancellation.unwrap_or_default(),
+            ..Self::default()
+        })
+    }
+
+    /// Returns the position of the reader in the archive file.
+    pub fn position(&self) -> u64 {
+        self.position.load(Ordering::Relaxed)
+    }
+
+    /// Returns the size of the archive file.
+    pub fn len(&self) -> u64 {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if processing the archive has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Returns the position of the reader in the uncompressed tarball.
+    pub fn tarball_position(&self) -> u64 {
+        self.tarball().position
+    }
+
+    /// Starts recording everything read from the uncompressed tarball at and
+    /// after `position`, i.e. the headers of the next entry.
+    pub fn record_from(&self, position: u64) {
+        self.tarball().recording = Some((position, vec![]));
+    }
+
+    /// Stops recording and returns what has been recorded.
+    pub fn take_recording(&self) -> Vec<u8> {
+        self.tarball()
+            .recording
+            .take()
+            .map(|(_, recorded)| recorded)
+            .unwrap_or_default()
+    }
+
+    fn tarball(&self) -> MutexGuard<'_, Tarball> {
+        // the state is consistent after every update
+        self.tarball.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Archive file reader, which reads the volumes of a split archive one after
+/// another.
+#[derive(Debug)]
+pub struct Source {
+    volumes: Vec<Volume>,
+    current: usize,
+    pos: u64,
+    state: Arc<State>,
+}
+
+/// Volume of an archive file.
+#[derive(Debug)]
+struct Volume {
+    file: File,
+
+    /// Position of the volume in the archive file.
+    start: u64,
+
+    /// Length of the volume headers at the start of the volume, which are
+    /// not part of the archive file.
+    headers: u64,
+}
+
+impl Source {
+    /// Opens the archive file that consists of the volumes at `paths`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if opening a file fails.
+    pub fn open(paths: &[PathBuf], state: &Arc<State>) -> io::Result<Self> {
+        let volumes = paths
+            .iter()
+            .map(|path| {
+                Ok(Volume {
+                    file: File::open(path)?,
+                    start: 0,
+                    headers: 0,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        let mut source = Self {
+            volumes,
+            current: 0,
+            pos: 0,
+            state: Arc::clone(state),
+        };
+
+        source.locate()?;
+        Ok(source)
+    }
+
+    /// Skips the volume label and continuation headers that GNU tar writes
+    /// at the start of each volume of a multi-volume tarball, so that the
+    /// volumes read as one tarball.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the headers fails.
+    pub fn gnu_volumes(mut self) -> io::Result<Self> {
+        for volume in &mut self.volumes {
+            volume.headers = gnu_volume_headers(&mut volume.file)?;
+        }
+
+        self.locate()?;
+        Ok(self)
+    }
+
+    /// Computes the positions of the volumes and moves to the start.
+    fn locate(&mut self) -> io::Result<()> {
+        let mut start = 0;
+
+        for volume in &mut self.volumes {
+            let len = volume.file.metadata()?.len();
+
+            volume.start = start;
+            start += len.saturating_sub(volume.headers);
+        }
+
+        self.state.len.store(start, Ordering::Relaxed);
+        self.seek(SeekFrom::Start(0))?;
+
+        Ok(())
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.state.is_cancelled() {
+            return Err(io::Error::other("archive processing was cancelled"));
+        }
+
+        loop {
+            let n = self.volumes[self.current].file.read(buf)?;
+
+            if n == 0
+                && !buf.is_empty()
+                && self.current + 1 < self.volumes.len()
+            {
+                self.current += 1;
+
+                let volume = &mut self.volumes[self.current];
+                volume.file.seek(SeekFrom::Start(volume.headers))?;
+
+                continue;
+            }
+
+            self.pos += n as u64;
+            self.state.position.store(self.pos, Ordering::Relaxed);
+
+            return Ok(n);
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.state.len();
+
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        self.current = self
+            .volumes
+            .iter()
+            .rposition(|volume| volume.start <= target)
+            .unwrap_or_default();
+
+        let volume = &mut self.volumes[self.current];
+        let offset = volume.headers + (target - volume.start);
+        volume.file.seek(SeekFrom::Start(offset))?;
+
+        self.pos = target;
+        self.state.position.store(self.pos, Ordering::Relaxed);
+
+        Ok(self.pos)
+    }
+}
+
+/// Reader of the uncompressed tarball, which tracks its position in the
+/// [`State`] and records what is read while requested.
+#[derive(Debug)]
+pub struct Tracked<R> {
+    reader: R,
+    state: Arc<State>,
+}
+
+impl<R> Tracked<R> {
+    /// Returns a reader of the tarball read from `reader`, starting at
+    /// position 0.
+    pub fn new(reader: R, state: &Arc<State>) -> Self {
+        *state.tarball() = Tarball::default();
+
+        Self {
+            reader,
+            state: Arc::clone(state),
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+
+        let mut tarball = self.state.tarball();
+        let start = tarball.position;
+        let end = start + n as u64;
+        tarball.position = end;
+
+        if let Some((from, recorded)) = &mut tarball.recording {
+            if end > *from {
+                let skip = usize::try_from(from.saturating_sub(start))
+                    .unwrap_or(usize::MAX);
+                recorded.extend_from_slice(&buf[skip.min(n)..n]);
+            }
+        }
+
+        drop(tarball);
+
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Tracked<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = self.reader.seek(pos)?;
+        self.state.tarball().position = position;
+        Ok(position)
+    }
+}
+
+/// Size of tar blocks.
+pub const BLOCK: usize = 512;
+
+/// Type of GNU tar volume label headers.
+const VOLUME_LABEL: u8 = b'V';
+
+/// Type of GNU tar headers of entries continued from the previous volume.
+const CONTINUATION: u8 = b'M';
+
+/// Returns the length of the GNU tar volume label and continuation headers
+/// at the start of `file`.
+fn gnu_volume_headers(file: &mut File) -> io::Result<u64> {
+    let mut len = 0;
+
+    loop {
+        file.seek(SeekFrom::Start(len))?;
+
+        let mut block = [0; BLOCK];
+        let n = read_full(file, &mut block)?;
+
+        match gnu_volume_header(&block[..n]) {
+            Some((VOLUME_LABEL, size)) => {
+                len += BLOCK as u64 + size.next_multiple_of(BLOCK as u64);
+            }
+
+            Some((CONTINUATION, _)) => return Ok(len + BLOCK as u64),
+            _ => return Ok(len),
+        }
+    }
+}
+
+/// Returns the type and size of `block` if it is a GNU tar volume label or
+/// continuation header.
+fn gnu_volume_header(block: &[u8]) -> Option<(u8, u64)> {
+    if block.len() != BLOCK {
+        return None;
+    }
+
+    let entry_type = block[156];
+
+    if entry_type != VOLUME_LABEL && entry_type != CONTINUATION {
+        return None;
+    }
+
+    if !is_header(block) {
+        return None;
+    }
+
+    Some((entry_type, octal(&block[124..136])?))
+}
+
+/// Returns true if `block` is a tar header with a valid checksum.
+pub fn is_header(block: &[u8]) -> bool {
+    if block.len() != BLOCK {
+        return false;
+    }
+
+    let sum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                u64::from(*b)
+            }
+        })
+        .sum();
+
+    octal(&block[148..156]) == Some(sum)
+}
+
+/// Returns the number in the octal header field `field`, which is zero if
+/// the field is empty.
+fn octal(field: &[u8]) -> Option<u64> {
+    let field = std::str::from_utf8(field).ok()?;
+    let field = field.trim_matches(|c: char| c == ' ' || c == '\0');
+
+    if field.is_empty() {
+        return Some(0);
+    }
+
+    u64::from_str_radix(field, 8).ok()
+}
+
+/// Reads into `buf` until it is full or the end of `reader`.
+pub fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(n)
+}
@@ -0,0 +1,439 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use tar::{GnuExtSparseHeader, GnuSparseHeader};
+
+use crate::entry::unpack_file;
+use crate::source::BLOCK;
+use crate::{Entry, Error, Result};
+
+/// Format of the map of a sparse file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// NONEXHAUSTIVE new formats could add new sparse formats
+#[non_exhaustive]
+pub enum SparseFormat {
+    /// Old GNU format, entry type `S` with the map in the header and
+    /// extension headers.
+    OldGnu,
+
+    /// PAX format 0.0, with `GNU.sparse.offset` and `GNU.sparse.numbytes`
+    /// records.
+    Pax00,
+
+    /// PAX format 0.1, with a `GNU.sparse.map` record.
+    Pax01,
+
+    /// PAX format 1.0, with the map at the start of the contents.
+    Pax10,
+}
+
+/// Region of a sparse file that holds data, everything else is a hole.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SparseRegion {
+    /// Offset of the data in the file.
+    pub offset: u64,
+
+    /// Length of the data in bytes.
+    pub len: u64,
+}
+
+/// Map of the data regions of a sparse file, see [`Entry::sparse_map`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SparseMap {
+    /// Format the map is stored in.
+    pub format: SparseFormat,
+
+    /// Regions that hold data, sorted by offset.
+    pub regions: Vec<SparseRegion>,
+
+    /// Size of the file including holes, as reported by
+    /// [`Entry::size`](crate::Entry::size).
+    pub apparent_size: u64,
+}
+
+impl SparseMap {
+    /// Returns the map of `regions`, which must be sorted, must not overlap
+    /// and must be within `apparent_size`.
+    fn new(
+        format: SparseFormat,
+        regions: Vec<SparseRegion>,
+        apparent_size: u64,
+    ) -> Result<Self> {
+        let mut end = 0;
+
+        for region in &regions {
+            if region.offset < end {
+                return Err(Error::InvalidSparseMap(format!(
+                    "region at {} is out of order or overlaps",
+                    region.offset
+                )));
+            }
+
+            end = region
+                .offset
+                .checked_add(region.len)
+                .filter(|&end| end <= apparent_size)
+                .ok_or_else(|| {
+                    Error::InvalidSparseMap(format!(
+                        "region at {} extends past the size of {}",
+                        region.offset, apparent_size
+                    ))
+                })?;
+        }
+
+        Ok(Self {
+            format,
+            regions,
+            apparent_size,
+        })
+    }
+
+    /// Returns the number of bytes of data stored in the archive.
+    #[must_use]
+    pub fn stored_size(&self) -> u64 {
+        self.regions.iter().map(|region| region.len).sum()
+    }
+}
+
+/// Sparse file state of an entry.
+pub struct Sparse {
+    map: SparseMap,
+
+    /// Path of PAX sparse files, whose header holds a made-up path.
+    name: Option<PathBuf>,
+
+    /// Position in the contents including holes.
+    pos: u64,
+}
+
+impl Sparse {
+    /// Returns the sparse file state of `entry`, or `None` if it is not a
+    /// sparse file, reading the map at the start of PAX 1.0 contents.
+    ///
+    /// Extension headers of old GNU sparse files are taken from `headers`,
+    /// everything read from the tarball for the entry up to its data.
+    pub fn of(entry: &mut Entry<'_>, headers: &[u8]) -> Result<Option<Self>> {
+        let entry_type = entry.header().entry_type();
+
+        if entry_type.is_gnu_sparse() {
+            return Ok(Some(Self {
+                map: old_gnu(entry, headers)?,
+                name: None,
+                pos: 0,
+            }));
+        }
+
+        if !entry_type.is_file() {
+            return Ok(None);
+        }
+
+        let extensions = entry.pax_extensions()?;
+
+        if !extensions
+            .iter()
+            .any(|(key, _)| key.starts_with("GNU.sparse."))
+        {
+            return Ok(None);
+        }
+
+        let record = |key: &str| {
+            extensions
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_slice())
+        };
+
+        let version = (record("GNU.sparse.major"), record("GNU.sparse.minor"));
+
+        let (format, regions, size) = match version {
+            (Some(b"1"), Some(b"0")) => {
+                let regions = data_map(entry)?;
+                (SparseFormat::Pax10, regions, record("GNU.sparse.realsize"))
+            }
+
+            (Some(major), minor) => {
+                return Err(Error::InvalidSparseMap(format!(
+                    "unsupported version {}.{}",
+                    String::from_utf8_lossy(major),
+                    String::from_utf8_lossy(minor.unwrap_or_default()),
+                )));
+            }
+
+            (None, _) => match record("GNU.sparse.map") {
+                Some(map) => {
+                    let numbers = map
+                        .split(|&b| b == b',')
+                        .map(number)
+                        .collect::<Result<Vec<_>>>()?;
+
+                    (
+                        SparseFormat::Pax01,
+                        regions(&numbers)?,
+                        record("GNU.sparse.size"),
+                    )
+                }
+
+                None => (
+                    SparseFormat::Pax00,
+                    pax_regions(&extensions)?,
+                    record("GNU.sparse.size"),
+                ),
+            },
+        };
+
+        let size = size.ok_or_else(|| {
+            Error::InvalidSparseMap("missing real size".to_owned())
+        })?;
+
+        let name = match record("GNU.sparse.name") {
+            Some(name) => {
+                PathBuf::from(String::from_utf8_lossy(name).as_ref())
+            }
+            None => entry.path()?.into_owned(),
+        };
+
+        Ok(Some(Self {
+            map: SparseMap::new(format, regions, number(size)?)?,
+            name: Some(name),
+            pos: 0,
+        }))
+    }
+
+    /// Returns the map of the sparse file.
+    pub const fn map(&self) -> &SparseMap {
+        &self.map
+    }
+
+    /// Returns the path of PAX sparse files.
+    pub fn name(&self) -> Option<&Path> {
+        self.name.as_deref()
+    }
+
+    /// Reads the contents of the sparse file `entry`, with holes read as
+    /// zeros.
+    pub fn read(
+        &mut self,
+        entry: &mut Entry<'_>,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        // tar reads the holes of old GNU sparse files as zeros itself
+        if self.map.format == SparseFormat::OldGnu {
+            return entry.read(buf);
+        }
+
+        let remaining = self.map.apparent_size.saturating_sub(self.pos);
+
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let regions = &self.map.regions;
+        let next = regions.partition_point(|r| r.offset + r.len <= self.pos);
+
+        let n = match regions.get(next) {
+            Some(region) if region.offset <= self.pos => {
+                let len = region.offset + region.len - self.pos;
+                let len = limit(buf, len.min(remaining));
+
+                match entry.read(&mut buf[..len])? {
+                    0 => return Err(truncated()),
+                    n => n,
+                }
+            }
+
+            region => {
+                let end = region.map_or(self.map.apparent_size, |region| {
+                    region.offset.min(self.map.apparent_size)
+                });
+
+                let len = limit(buf, end - self.pos);
+                buf[..len].fill(0);
+                len
+            }
+        };
+
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+
+    /// Extracts the PAX sparse file `entry` into the directory `dst` like
+    /// [`unpack_file`], seeking over the holes instead of writing them.
+    pub fn unpack_in(
+        &self,
+        entry: &mut Entry<'_>,
+        dst: &Path,
+    ) -> io::Result<bool> {
+        let name = self.name.as_deref().unwrap_or_else(|| Path::new(""));
+        let header = entry.header().clone();
+
+        unpack_file(dst, name, &header, |file| {
+            for region in &self.map.regions {
+                file.seek(SeekFrom::Start(region.offset))?;
+
+                let n = io::copy(&mut entry.by_ref().take(region.len), file)?;
+
+                if n != region.len {
+                    return Err(truncated());
+                }
+            }
+
+            file.set_len(self.map.apparent_size)
+        })
+    }
+}
+
+/// Returns the map of the old GNU sparse file `entry`, whose extension
+/// headers follow its header at the end of `headers`.
+fn old_gnu(entry: &Entry<'_>, headers: &[u8]) -> Result<SparseMap> {
+    let header = entry.header();
+
+    let gnu = header.as_gnu().ok_or_else(|| {
+        Error::InvalidSparseMap("sparse entry without GNU header".to_owned())
+    })?;
+
+    let mut regions = vec![];
+    push_regions(&mut regions, &gnu.sparse)?;
+
+    if gnu.is_extended() {
+        let missing =
+            || Error::InvalidSparseMap("missing extension headers".to_owned());
+
+        let blocks = headers.chunks_exact(BLOCK);
+        let extensions = blocks
+            .clone()
+            .rposition(|block| block == header.as_bytes())
+            .map(|i| blocks.skip(i + 1))
+            .ok_or_else(missing)?;
+
+        let mut extended = true;
+
+        for block in extensions {
+            let mut extension = GnuExtSparseHeader::new();
+            extension.as_mut_bytes().copy_from_slice(block);
+            push_regions(&mut regions, extension.sparse())?;
+
+            extended = extension.is_extended();
+        }
+
+        if extended {
+            return Err(missing());
+        }
+    }
+
+    SparseMap::new(SparseFormat::OldGnu, regions, gnu.real_size()?)
+}
+
+/// Appends the regions of the old GNU sparse `headers` that hold data.
+fn push_regions(
+    regions: &mut Vec<SparseRegion>,
+    headers: &[GnuSparseHeader],
+) -> io::Result<()> {
+    for header in headers.iter().take_while(|header| !header.is_empty()) {
+        let region = SparseRegion {
+            offset: header.offset()?,
+            len: header.length()?,
+        };
+
+        if region.len > 0 {
+            regions.push(region);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the regions of the PAX 0.0 `GNU.sparse.offset` and
+/// `GNU.sparse.numbytes` records, which alternate.
+fn pax_regions(extensions: &[(String, Vec<u8>)]) -> Result<Vec<SparseRegion>> {
+    let mut numbers = vec![];
+
+    for (key, value) in extensions {
+        match key.as_str() {
+            "GNU.sparse.offset" if numbers.len().is_multiple_of(2) => {}
+            "GNU.sparse.numbytes" if !numbers.len().is_multiple_of(2) => {}
+            "GNU.sparse.offset" | "GNU.sparse.numbytes" => {
+                return Err(Error::InvalidSparseMap(format!(
+                    "unexpected {key}"
+                )));
+            }
+            _ => continue,
+        }
+
+        numbers.push(number(value)?);
+    }
+
+    regions(&numbers)
+}
+
+/// Reads the PAX 1.0 map at the start of the contents of `entry`, the number
+/// of regions and their offsets and lengths in decimal lines, padded to a
+/// multiple of the block size.
+fn data_map(entry: &mut Entry<'_>) -> Result<Vec<SparseRegion>> {
+    let mut numbers: Vec<u64> = vec![];
+    let mut line = vec![];
+    let mut block = [0; BLOCK];
+
+    loop {
+        entry.read_exact(&mut block)?;
+
+        for &b in &block {
+            if b != b'\n' {
+                line.push(b);
+                continue;
+            }
+
+            numbers.push(number(&line)?);
+            line.clear();
+
+            let count = numbers[0];
+
+            if numbers.len() as u64 > count.saturating_mul(2) {
+                return regions(&numbers[1..]);
+            }
+        }
+    }
+}
+
+/// Returns the regions of alternating offsets and lengths.
+fn regions(numbers: &[u64]) -> Result<Vec<SparseRegion>> {
+    if !numbers.len().is_multiple_of(2) {
+        return Err(Error::InvalidSparseMap(
+            "offset without length".to_owned(),
+        ));
+    }
+
+    Ok(numbers
+        .chunks_exact(2)
+        .map(|pair| SparseRegion {
+            offset: pair[0],
+            len: pair[1],
+        })
+        .filter(|region| region.len > 0)
+        .collect())
+}
+
+/// Returns the decimal number `value`.
+fn number(value: &[u8]) -> Result<u64> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| {
+            Error::InvalidSparseMap(format!(
+                "invalid number {:?}",
+                String::from_utf8_lossy(value)
+            ))
+        })
+}
+
+/// Returns how many bytes of `buf` to fill with at most `n` bytes.
+fn limit(buf: &[u8], n: u64) -> usize {
+    buf.len().min(n.try_into().unwrap_or(usize::MAX))
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "sparse file is truncated")
+}
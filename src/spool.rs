@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+
+/// Anonymous temporary file that contents are appended to while reading an
+/// archive, to be read back in a different order.
+pub struct Spool {
+    file: BufWriter<File>,
+    len: u64,
+}
+
+impl Spool {
+    /// Returns an empty spool.
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(tempfile::tempfile()?),
+            len: 0,
+        })
+    }
+
+    /// Appends everything read from `reader` and returns its offset.
+    pub fn push(&mut self, reader: &mut impl Read) -> io::Result<u64> {
+        // readers share the position of the file and may have moved it
+        self.file.seek(SeekFrom::End(0))?;
+
+        let offset = self.len;
+        self.len += io::copy(reader, &mut self.file)?;
+        self.file.flush()?;
+
+        Ok(offset)
+    }
+
+    /// Returns a reader of the `size` bytes at `offset`, while appending
+    /// goes on.
+    pub fn reader(&self, offset: u64, size: u64) -> io::Result<SpoolReader> {
+        Ok(SpoolReader {
+            file: self.file.get_ref().try_clone()?,
+            position: offset,
+            end: offset + size,
+        })
+    }
+
+    /// Finishes appending and returns the file to read the contents from.
+    pub fn finish(self) -> io::Result<Spooled> {
+        let file = self.file.into_inner().map_err(io::Error::from)?;
+        Ok(Spooled { file })
+    }
+}
+
+/// Spool that has been written, see [`Spool::finish`].
+pub struct Spooled {
+    file: File,
+}
+
+impl Spooled {
+    /// Returns the `size` bytes at `offset`.
+    pub fn read(
+        &mut self,
+        offset: u64,
+        size: u64,
+    ) -> io::Result<impl Read + '_> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        Ok(Read::take(&mut self.file, size))
+    }
+}
+
+/// Reader of contents in a spool, see [`Spool::reader`].
+pub struct SpoolReader {
+    file: File,
+    position: u64,
+    end: u64,
+}
+
+impl Read for SpoolReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = usize::try_from(self.end - self.position)
+            .unwrap_or(usize::MAX)
+            .min(buf.len());
+
+        if left == 0 {
+            return Ok(0);
+        }
+
+        // the spool and other readers share the position of the file
+        self.file.seek(SeekFrom::Start(self.position))?;
+
+        let n = self.file.read(&mut buf[..left])?;
+        self.position += n as u64;
+
+        Ok(n)
+    }
+}
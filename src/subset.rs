@@ -0,0 +1,278 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "glob")]
+use globset::GlobSet;
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::entry::normalize;
+use crate::filter::Filter;
+use crate::spool::Spool;
+use crate::support::Format;
+use crate::{Archive, Builder, EntryType, Result};
+
+/// Rules that select and rename the entries of an archive to write them to a
+/// new archive, like `tar --exclude --transform`.
+///
+/// Entries are streamed from the source to the destination one at a time, so
+/// neither archive has to fit into memory. Only the contents of dropped files
+/// are spooled to disk, in case kept hard links point to them.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// use archive_rs::support::Format;
+/// use archive_rs::{Archive, Subset, Wildcards};
+///
+/// let exclude = Wildcards::new()
+///     .anchored(false)
+///     .add("docs")
+///     .add("*.pdb")
+///     .build()?;
+///
+/// let mut src = Archive::open("release.tar.gz")?;
+/// let dst = BufWriter::new(File::create("release-slim.tar.zst")?);
+///
+/// Subset::new()
+///     .excluding(exclude)
+///     .export(&mut src, Format::TarZstd, dst)?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct Subset {
+    filters: Vec<Filter>,
+
+    #[cfg(feature = "regex")]
+    renames: Vec<(Regex, String)>,
+}
+
+impl Subset {
+    /// Returns rules that keep every entry at its path.
+    pub const fn new() -> Self {
+        Self {
+            filters: vec![],
+
+            #[cfg(feature = "regex")]
+            renames: vec![],
+        }
+    }
+
+    /// Keeps only entries whose path matches one of `globs`.
+    #[cfg(feature = "glob")]
+    pub fn matching(mut self, globs: GlobSet) -> Self {
+        self.filters.push(Filter::Matching(globs));
+        self
+    }
+
+    /// Drops entries whose path matches one of `globs`.
+    #[cfg(feature = "glob")]
+    pub fn excluding(mut self, globs: GlobSet) -> Self {
+        self.filters.push(Filter::Excluding(globs));
+        self
+    }
+
+    /// Keeps only entries whose path matches `regex`.
+    #[cfg(feature = "regex")]
+    pub fn matching_regex(mut self, regex: Regex) -> Self {
+        self.filters.push(Filter::MatchingRegex(regex));
+        self
+    }
+
+    /// Drops entries whose path matches `regex`.
+    #[cfg(feature = "regex")]
+    pub fn excluding_regex(mut self, regex: Regex) -> Self {
+        self.filters.push(Filter::ExcludingRegex(regex));
+        self
+    }
+
+    /// Replaces the first match of `regex` in the path of each kept entry
+    /// with `replacement`, like `tar --transform 's/regex/replacement/'`.
+    ///
+    /// `replacement` may refer to capture groups, see
+    /// [`Regex::replace`]. Renames are applied in the order they were added,
+    /// after filtering, and entries whose path becomes empty are dropped.
+    #[cfg(feature = "regex")]
+    pub fn rename(
+        mut self,
+        regex: Regex,
+        replacement: impl Into<String>,
+    ) -> Self {
+        self.renames.push((regex, replacement.into()));
+        self
+    }
+
+    /// Writes the kept entries of `src`, renamed and with their metadata and
+    /// contents unchanged, as an archive of `format` to `writer`, and
+    /// returns the writer.
+    ///
+    /// Targets of hard links are renamed like paths. The first kept hard
+    /// link to a dropped file is written as a regular file with its contents,
+    /// which are spooled to an anonymous temporary file when the file is
+    /// dropped, and later hard links point to it. Targets of symbolic links
+    /// are kept as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `format` cannot be written, or if reading `src` or
+    /// writing fails.
+    pub fn export<W: Write>(
+        &self,
+        src: &mut Archive,
+        format: Format,
+        writer: W,
+    ) -> Result<W> {
+        let mut builder = Builder::new(writer, format)?;
+
+        // written paths of the entries that hard links can point to
+        let mut written: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+        // offsets and sizes of the contents of dropped files in the spool
+        let mut spool = Spool::new()?;
+        let mut dropped: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+
+        for entry in src.entries()? {
+            let mut entry = entry?;
+            let path = normalize(&entry.path()?);
+            let link_name = entry.link_name()?.map(Cow::into_owned);
+
+            // the path of the target of a hard link
+            let target = link_name
+                .as_deref()
+                .filter(|_| entry.entry_type() == EntryType::Link)
+                .map(normalize);
+
+            let renamed = if self.filters.iter().all(|f| f.accepts(&path)) {
+                self.renamed(&path)
+            } else {
+                None
+            };
+
+            let Some(renamed) = renamed else {
+                if entry.entry_type().is_file_or_sparse() {
+                    let size = entry.size();
+                    let offset = spool.push(&mut entry)?;
+                    dropped.insert(path, (offset, size));
+                } else if let Some(target) = target {
+                    // a dropped hard link has the contents of its target
+                    if let Some(&contents) = dropped.get(&target) {
+                        dropped.insert(path, contents);
+                    } else if let Some(written_target) = written.get(&target) {
+                        written.insert(path, written_target.clone());
+                    }
+                }
+
+                continue;
+            };
+
+            let Some(target) = target else {
+                builder.append(&mut entry, &renamed, link_name.as_deref())?;
+                written.insert(path, renamed);
+                continue;
+            };
+
+            if let Some(written_target) = written.get(&target) {
+                let written_target = written_target.clone();
+                builder.append(&mut entry, &renamed, Some(&written_target))?;
+                written.insert(path, written_target);
+                continue;
+            }
+
+            let Some((offset, size)) = dropped.remove(&target) else {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    path = %path.display(),
+                    target = %target.display(),
+                    "dropped hard link to a missing entry",
+                );
+
+                continue;
+            };
+
+            builder.append_unlinked(
+                entry.header().clone(),
+                &entry.pax_extensions()?,
+                &renamed,
+                size,
+                spool.reader(offset, size)?,
+            )?;
+
+            written.insert(target, renamed.clone());
+            written.insert(path, renamed);
+        }
+
+        builder.finish()
+    }
+
+    /// Returns `path` with all renames applied, or `None` if it is empty.
+    #[cfg(feature = "regex")]
+    fn renamed(&self, path: &Path) -> Option<PathBuf> {
+        if self.renames.is_empty() {
+            return Some(path.to_owned());
+        }
+
+        let mut path = path.to_string_lossy().into_owned();
+
+        for (regex, replacement) in &self.renames {
+            path = regex.replace(&path, replacement.as_str()).into_owned();
+        }
+
+        let path = path.trim_start_matches('/');
+
+        if path.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(path))
+        }
+    }
+
+    /// Returns `path`, as there are no renames without the regex feature.
+    #[cfg(not(feature = "regex"))]
+    // ALLOW the signature matches the one with the regex feature
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    fn renamed(&self, path: &Path) -> Option<PathBuf> {
+        Some(path.to_owned())
+    }
+}
+
+#[cfg(all(test, feature = "glob"))]
+mod tests {
+    use tar::EntryType;
+
+    use super::*;
+    use crate::testing::{self, Listed};
+    use crate::Wildcards;
+
+    #[test]
+    fn hard_links_to_dropped_files_hold_the_contents() {
+        let tarball = testing::tarball(|builder| {
+            testing::file(builder, "docs/a", b"a");
+            testing::link(builder, EntryType::Link, "bin/a", "docs/a");
+            testing::link(builder, EntryType::Link, "bin/b", "docs/a");
+            testing::link(builder, EntryType::Symlink, "bin/c", "./b");
+        });
+
+        let (_file, mut archive) = testing::open(&tarball, ".tar");
+        let exclude = Wildcards::new().add("docs").build().unwrap();
+
+        let subset = Subset::new()
+            .excluding(exclude)
+            .export(&mut archive, Format::Tar, vec![])
+            .unwrap();
+
+        assert_eq!(
+            testing::list(&subset),
+            [
+                Listed::new("bin/a", EntryType::Regular, b"a", None),
+                Listed::new("bin/b", EntryType::Link, b"", Some("bin/a")),
+                Listed::new("bin/c", EntryType::Symlink, b"", Some("./b")),
+            ]
+        );
+    }
+}
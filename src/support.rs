@@ -2,9 +2,14 @@
 //! features.
 
 use std::fmt;
+use std::path::Path;
+
+use path_utils::PathExt;
+
+use crate::volume::is_volume_number;
 
 /// Supported formats.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Format {
     #[cfg(feature = "tar")]
     /// tarball
@@ -35,6 +40,81 @@ pub enum Format {
 }
 
 impl Format {
+    /// Returns the format of the archive file at `path` by its file endings,
+    /// see [`Format::file_endings`], or `None` if it is not supported.
+    ///
+    /// A trailing volume number, e.g. `.000`, is ignored.
+    #[must_use]
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extensions: Vec<String> =
+            path.as_ref().extensions_lossy().collect();
+        let extensions: Vec<&str> =
+            extensions.iter().map(String::as_str).collect();
+
+        let extensions = match extensions.as_slice() {
+            [number, rest @ ..] if is_volume_number(number) => rest,
+            all => all,
+        };
+
+        match extensions {
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "bzip2", feature = "bzip2-rs")
+            ))]
+            ["tbz" | "tbz2", ..] | ["bz2", "tar", ..] => Some(Self::TarBzip2),
+
+            #[cfg(all(feature = "tar", feature = "flate2"))]
+            ["tgz", ..] | ["gz", "tar", ..] => Some(Self::TarGzip),
+
+            #[cfg(all(feature = "tar", feature = "lz4"))]
+            ["lz4", "tar", ..] => Some(Self::TarLz4),
+
+            #[cfg(all(feature = "tar", feature = "xz2"))]
+            ["txz", ..] | ["xz", "tar", ..] => Some(Self::TarXz),
+
+            #[cfg(all(feature = "tar", feature = "zstd"))]
+            ["zst", "tar", ..] => Some(Self::TarZstd),
+
+            #[cfg(feature = "tar")]
+            ["tar", ..] => Some(Self::Tar),
+
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the archive that starts with `head` by its
+    /// magic bytes, or `None` if it is not supported.
+    ///
+    /// Compressed tarballs are detected by the magic bytes of the
+    /// compression only, and tarballs need the first 262 bytes.
+    #[must_use]
+    pub fn from_magic(head: &[u8]) -> Option<Self> {
+        match head {
+            #[cfg(all(
+                feature = "tar",
+                any(feature = "bzip2", feature = "bzip2-rs")
+            ))]
+            [b'B', b'Z', b'h', ..] => Some(Self::TarBzip2),
+
+            #[cfg(all(feature = "tar", feature = "flate2"))]
+            [0x1f, 0x8b, ..] => Some(Self::TarGzip),
+
+            #[cfg(all(feature = "tar", feature = "lz4"))]
+            [0x04, 0x22, 0x4d, 0x18, ..] => Some(Self::TarLz4),
+
+            #[cfg(all(feature = "tar", feature = "xz2"))]
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Self::TarXz),
+
+            #[cfg(all(feature = "tar", feature = "zstd"))]
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::TarZstd),
+
+            #[cfg(feature = "tar")]
+            _ if head.get(257..262) == Some(b"ustar") => Some(Self::Tar),
+
+            _ => None,
+        }
+    }
+
     /// Returns the format name.
     #[must_use]
     pub const fn name(&self) -> &'static str {
@@ -0,0 +1,164 @@
+//! Fixtures of the unit tests.
+
+use std::io::{Read, Write};
+
+use tempfile::NamedTempFile;
+
+use crate::Archive;
+
+/// Entry of a tarball read back by [`list`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Listed {
+    pub path: String,
+    pub entry_type: tar::EntryType,
+    pub data: Vec<u8>,
+    pub link_name: Option<String>,
+}
+
+impl Listed {
+    /// Returns an entry at `path` of `entry_type` with contents `data`,
+    /// pointing to `link_name` if it is a link.
+    pub fn new(
+        path: &str,
+        entry_type: tar::EntryType,
+        data: &[u8],
+        link_name: Option<&str>,
+    ) -> Self {
+        Self {
+            path: path.to_owned(),
+            entry_type,
+            data: data.to_vec(),
+            link_name: link_name.map(str::to_owned),
+        }
+    }
+}
+
+/// Returns a tarball with the entries that `build` appends.
+pub fn tarball(build: impl FnOnce(&mut tar::Builder<Vec<u8>>)) -> Vec<u8> {
+    let mut builder = tar::Builder::new(vec![]);
+    build(&mut builder);
+    builder.into_inner().unwrap()
+}
+
+/// Appends an entry of `entry_type` with the raw name `name`, which may be
+/// `./`, and contents `data`.
+pub fn entry(
+    builder: &mut tar::Builder<impl Write>,
+    entry_type: tar::EntryType,
+    name: &str,
+    data: &[u8],
+) {
+    let mut header = tar::Header::new_gnu();
+    header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+    header.set_entry_type(entry_type);
+    header.set_mode(if entry_type.is_dir() { 0o755 } else { 0o644 });
+    header.set_uid(1000);
+    header.set_gid(1000);
+    header.set_mtime(1_700_000_000);
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    builder.append(&header, data).unwrap();
+}
+
+/// Appends a regular file at `name` with contents `data`.
+pub fn file(builder: &mut tar::Builder<impl Write>, name: &str, data: &[u8]) {
+    entry(builder, tar::EntryType::Regular, name, data);
+}
+
+/// Appends a link of `entry_type` at `name` to `target`.
+pub fn link(
+    builder: &mut tar::Builder<impl Write>,
+    entry_type: tar::EntryType,
+    name: &str,
+    target: &str,
+) {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    header.set_uid(1000);
+    header.set_gid(1000);
+    header.set_mtime(1_700_000_000);
+    header.set_size(0);
+    builder.append_link(&mut header, name, target).unwrap();
+}
+
+/// Appends an old GNU sparse file at `name` of `size` bytes, with the data
+/// regions `regions` of offsets and contents.
+pub fn sparse(
+    builder: &mut tar::Builder<impl Write>,
+    name: &str,
+    size: u64,
+    regions: &[(u64, &[u8])],
+) {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::GNUSparse);
+    header.set_path(name).unwrap();
+    header.set_mode(0o644);
+    header.set_uid(1000);
+    header.set_gid(1000);
+    header.set_mtime(1_700_000_000);
+
+    let gnu = header.as_gnu_mut().unwrap();
+    gnu.set_real_size(size);
+
+    for (sparse, (offset, data)) in gnu.sparse.iter_mut().zip(regions) {
+        sparse.set_offset(*offset);
+        sparse.set_length(data.len() as u64);
+    }
+
+    let data = regions.iter().flat_map(|(_, data)| *data);
+    let data: Vec<u8> = data.copied().collect();
+
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    builder.append(&header, data.as_slice()).unwrap();
+}
+
+/// Returns the contents of the sparse file of `size` bytes with the data
+/// regions `regions`.
+pub fn expand(size: u64, regions: &[(u64, &[u8])]) -> Vec<u8> {
+    let mut contents = vec![0; usize::try_from(size).unwrap()];
+
+    for (offset, data) in regions {
+        let offset = usize::try_from(*offset).unwrap();
+        contents[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    contents
+}
+
+/// Writes `bytes` to a temporary file whose name ends with `suffix`, and
+/// opens it as an archive.
+pub fn open(bytes: &[u8], suffix: &str) -> (NamedTempFile, Archive) {
+    let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+    file.write_all(bytes).unwrap();
+    file.flush().unwrap();
+
+    let archive = Archive::open(file.path()).unwrap();
+    (file, archive)
+}
+
+/// Returns the entries of the tarball `bytes`.
+pub fn list(bytes: &[u8]) -> Vec<Listed> {
+    let mut archive = tar::Archive::new(bytes);
+
+    archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let mut data = vec![];
+            entry.read_to_end(&mut data).unwrap();
+
+            Listed {
+                path: entry.path().unwrap().to_string_lossy().into_owned(),
+                entry_type: entry.header().entry_type(),
+                data,
+                link_name: entry
+                    .link_name()
+                    .unwrap()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            }
+        })
+        .collect()
+}
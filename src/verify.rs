@@ -0,0 +1,143 @@
+//! Verification of archive contents against a checksum manifest.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::entry::normalize;
+use crate::{digest, Algorithm, Archive, Manifest, Result};
+
+/// Result of verifying an archive against a [`Manifest`], see
+/// [`Archive::verify`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Verification {
+    /// Paths of the files whose digests match the manifest.
+    pub matched: Vec<PathBuf>,
+
+    /// Files whose digests differ from the manifest.
+    pub mismatched: Vec<Mismatch>,
+
+    /// Paths of the manifest that are not in the archive.
+    pub missing: Vec<PathBuf>,
+
+    /// Paths of files in the archive that are not in the manifest.
+    pub extra: Vec<PathBuf>,
+}
+
+impl Verification {
+    /// Returns true if all files of the manifest, and only those, are in the
+    /// archive with matching digests.
+    #[must_use]
+    pub const fn is_ok(&self) -> bool {
+        self.mismatched.is_empty()
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+    }
+}
+
+/// Digest of a file that differs from the manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Mismatch {
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// Algorithm of the differing digest.
+    pub algorithm: Algorithm,
+
+    /// Digest according to the manifest.
+    pub expected: String,
+
+    /// Digest of the file in the archive.
+    pub actual: String,
+}
+
+impl Archive {
+    /// Verifies the regular files of this archive, including sparse files,
+    /// against `manifest`, hashing each file with the algorithms of its
+    /// manifest entry in a single pass.
+    ///
+    /// Paths are compared without leading `./`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    ///
+    /// use archive_rs::{Algorithm, Archive, Manifest, ManifestFormat};
+    ///
+    /// let manifest = File::open("backup.sha256")?;
+    /// let manifest = Manifest::read(
+    ///     BufReader::new(manifest),
+    ///     ManifestFormat::Sum(Algorithm::Sha256),
+    /// )?;
+    ///
+    /// let mut archive = Archive::open("backup.tar.zst")?;
+    /// let verification = archive.verify(&manifest)?;
+    ///
+    /// for mismatch in &verification.mismatched {
+    ///     eprintln!("{}: FAILED", mismatch.path.display());
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the archive fails.
+    pub fn verify(&mut self, manifest: &Manifest) -> Result<Verification> {
+        let mut expected: HashMap<PathBuf, usize> = manifest
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (normalize(&entry.path), i))
+            .collect();
+
+        let mut verification = Verification::default();
+
+        for entry in self.entries()? {
+            let mut entry = entry?;
+
+            if !entry.entry_type().is_file_or_sparse() {
+                continue;
+            }
+
+            let path = normalize(&entry.path()?);
+
+            let Some(i) = expected.remove(&path) else {
+                verification.extra.push(path);
+                continue;
+            };
+
+            let digests = &manifest.entries()[i].digests;
+            let algorithms: Vec<Algorithm> = digests.keys().copied().collect();
+            let actual = digest(&mut entry, &algorithms)?;
+
+            let mismatch = digests.iter().find_map(|(algorithm, expected)| {
+                let actual = &actual[algorithm];
+
+                (actual != expected).then(|| Mismatch {
+                    path: path.clone(),
+                    algorithm: *algorithm,
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                })
+            });
+
+            match mismatch {
+                Some(mismatch) => verification.mismatched.push(mismatch),
+                None => verification.matched.push(path),
+            }
+        }
+
+        let mut missing: Vec<usize> = expected.into_values().collect();
+        missing.sort_unstable();
+
+        verification.missing = missing
+            .into_iter()
+            .map(|i| manifest.entries()[i].path.clone())
+            .collect();
+
+        Ok(verification)
+    }
+}
@@ -0,0 +1,452 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+
+use crate::entry::normalize;
+use crate::{Archive, Entry, EntryType, Error, Result};
+
+/// Maximum number of symbolic links followed while resolving a path, like
+/// `MAXSYMLINKS` of Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Read-only directory tree view of an archive, see [`Archive::into_fs`].
+///
+/// Paths are relative to the root of the archive, a leading `/` is ignored.
+/// Parent directories that are missing from the archive exist with
+/// permissions `0755`. Symbolic links are resolved within the archive, with
+/// absolute targets relative to its root, and hard links have the metadata
+/// and contents of their target. Of entries with the same path, the last one
+/// is visible, like when extracting the archive.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::io::Read;
+///
+/// use archive_rs::Archive;
+///
+/// let mut fs = Archive::open("rootfs.tar.zst")?.into_fs()?;
+///
+/// for entry in fs.read_dir("/etc")? {
+///     println!("{} {:o}", entry.path.display(), entry.metadata.mode);
+/// }
+///
+/// let mut os_release = String::new();
+/// fs.open("/etc/os-release")?.read_to_string(&mut os_release)?;
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+// ALLOW this is not a public module
+#[allow(clippy::module_name_repetitions)]
+pub struct ArchiveFs {
+    archive: Archive,
+    nodes: BTreeMap<PathBuf, Node>,
+}
+
+/// Entry of the directory tree.
+struct Node {
+    metadata: Metadata,
+
+    /// Offset of the entry that holds the contents.
+    offset: Option<u64>,
+    link_name: Option<PathBuf>,
+    children: BTreeSet<OsString>,
+}
+
+/// Metadata of an entry of an [`ArchiveFs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Metadata {
+    /// Type of the entry, never [`EntryType::Link`] or
+    /// [`EntryType::GNUSparse`], sparse files are regular files.
+    pub entry_type: EntryType,
+
+    /// Size of the contents in bytes.
+    pub len: u64,
+
+    /// Permissions.
+    pub mode: u32,
+
+    /// Owner.
+    pub uid: u64,
+
+    /// Group.
+    pub gid: u64,
+
+    /// Modification time in seconds since the Unix epoch.
+    pub mtime: u64,
+}
+
+impl Metadata {
+    /// Returns true if the entry is a directory.
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        self.entry_type == EntryType::Directory
+    }
+
+    /// Returns true if the entry is a regular file.
+    #[must_use]
+    pub fn is_file(&self) -> bool {
+        self.entry_type.is_file()
+    }
+
+    /// Returns true if the entry is a symbolic link.
+    #[must_use]
+    pub fn is_symlink(&self) -> bool {
+        self.entry_type == EntryType::Symlink
+    }
+
+    /// Returns the metadata of a directory that is missing from the archive.
+    const fn implicit_dir() -> Self {
+        Self {
+            entry_type: EntryType::Directory,
+            len: 0,
+            mode: 0o755,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+        }
+    }
+}
+
+/// Entry of a directory of an [`ArchiveFs`], see [`ArchiveFs::read_dir`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DirEntry {
+    /// Path of the entry, i.e. the directory joined with its file name.
+    pub path: PathBuf,
+
+    /// Metadata of the entry, without following symbolic links.
+    pub metadata: Metadata,
+}
+
+impl DirEntry {
+    /// Returns the file name of the entry.
+    #[must_use]
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or_default()
+    }
+}
+
+impl Archive {
+    /// Returns a read-only directory tree view of this archive, indexing its
+    /// entries once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the archive fails.
+    pub fn into_fs(mut self) -> Result<ArchiveFs> {
+        let mut nodes = BTreeMap::new();
+
+        nodes.insert(
+            PathBuf::new(),
+            Node {
+                metadata: Metadata::implicit_dir(),
+                offset: None,
+                link_name: None,
+                children: BTreeSet::new(),
+            },
+        );
+
+        for entry in self.entries()? {
+            let entry = entry?;
+            let path = relative(&entry.path()?);
+
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+
+            let node = if entry.entry_type() == EntryType::Link {
+                let Some(target) = linked(&entry, &nodes)? else {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        path = %path.display(),
+                        "skipped hard link to a missing entry",
+                    );
+
+                    continue;
+                };
+
+                target
+            } else {
+                Node {
+                    metadata: Metadata {
+                        entry_type: entry.entry_type().extracted(),
+                        len: entry.size(),
+                        mode: entry.mode()?,
+                        uid: entry.uid()?,
+                        gid: entry.gid()?,
+                        mtime: entry.mtime()?,
+                    },
+                    offset: Some(entry.offset()),
+                    link_name: entry.link_name()?.map(Cow::into_owned),
+                    children: BTreeSet::new(),
+                }
+            };
+
+            insert(&mut nodes, path, node);
+        }
+
+        Ok(ArchiveFs {
+            archive: self,
+            nodes,
+        })
+    }
+}
+
+/// Returns a node with the metadata and contents of the target of the hard
+/// link `entry`, or `None` if the target is missing.
+fn linked(
+    entry: &Entry<'_>,
+    nodes: &BTreeMap<PathBuf, Node>,
+) -> Result<Option<Node>> {
+    let Some(target) = entry.link_name()? else {
+        return Ok(None);
+    };
+
+    Ok(nodes.get(&relative(&target)).map(|target| Node {
+        metadata: target.metadata.clone(),
+        offset: target.offset,
+        link_name: target.link_name.clone(),
+        children: BTreeSet::new(),
+    }))
+}
+
+/// Inserts `node` at `path`, adding missing parent directories.
+fn insert(nodes: &mut BTreeMap<PathBuf, Node>, path: PathBuf, mut node: Node) {
+    let mut child = path.as_path();
+
+    while let Some(parent) = child.parent() {
+        let dir = nodes.entry(parent.to_owned()).or_insert_with(|| Node {
+            metadata: Metadata::implicit_dir(),
+            offset: None,
+            link_name: None,
+            children: BTreeSet::new(),
+        });
+
+        let name = child.file_name().unwrap_or_default().to_owned();
+
+        if !dir.children.insert(name) {
+            break;
+        }
+
+        child = parent;
+    }
+
+    if let Some(previous) = nodes.remove(&path) {
+        if node.metadata.is_dir() {
+            node.children = previous.children;
+        }
+    }
+
+    nodes.insert(path, node);
+}
+
+/// Returns `path` relative to the root of the archive, without `.`
+/// components and leading `/`.
+fn relative(path: &Path) -> PathBuf {
+    normalize(path)
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+impl ArchiveFs {
+    /// Returns the entries of the directory at `path`, sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry at `path` or if it is not a
+    /// directory.
+    pub fn read_dir(&self, path: impl AsRef<Path>) -> Result<Vec<DirEntry>> {
+        let path = path.as_ref();
+        let (resolved, node) = self.resolve(path, true)?;
+
+        if !node.metadata.is_dir() {
+            return Err(Error::NotADirectory(path.to_owned()));
+        }
+
+        Ok(node
+            .children
+            .iter()
+            .map(|name| DirEntry {
+                path: path.join(name),
+                metadata: self.nodes[&resolved.join(name)].metadata.clone(),
+            })
+            .collect())
+    }
+
+    /// Returns the metadata of the entry at `path`, following symbolic
+    /// links.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry at `path` or if resolving
+    /// symbolic links fails.
+    pub fn metadata(&self, path: impl AsRef<Path>) -> Result<Metadata> {
+        Ok(self.resolve(path.as_ref(), true)?.1.metadata.clone())
+    }
+
+    /// Returns the metadata of the entry at `path`, without following a
+    /// symbolic link at `path` itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry at `path` or if resolving
+    /// symbolic links fails.
+    pub fn symlink_metadata(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Metadata> {
+        Ok(self.resolve(path.as_ref(), false)?.1.metadata.clone())
+    }
+
+    /// Returns the target of the symbolic link at `path`, as stored in the
+    /// archive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry at `path` or if it is not a
+    /// symbolic link.
+    pub fn read_link(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let (_, node) = self.resolve(path, false)?;
+
+        match &node.link_name {
+            Some(target) if node.metadata.is_symlink() => Ok(target.clone()),
+            _ => Err(Error::NotASymlink(path.to_owned())),
+        }
+    }
+
+    /// Returns true if there is an entry at `path`, following symbolic
+    /// links.
+    #[must_use]
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        self.resolve(path.as_ref(), true).is_ok()
+    }
+
+    /// Returns the entry of the regular file at `path`, following symbolic
+    /// links, to read its contents.
+    ///
+    /// The archive is opened again for every file, and unless it can be
+    /// repositioned, see [`Archive::read_entry_at`], its entries are
+    /// streamed through up to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry at `path`, if it is not a
+    /// regular file or if reading the archive fails.
+    pub fn open(&mut self, path: impl AsRef<Path>) -> Result<Entry<'_>> {
+        let path = path.as_ref();
+        let (_, node) = self.resolve(path, true)?;
+
+        let offset = match node.offset {
+            Some(offset) if node.metadata.is_file() => offset,
+            _ => return Err(Error::NotAFile(path.to_owned())),
+        };
+
+        self.archive = self.archive.reopen()?;
+
+        self.archive
+            .read_entry_at(offset)?
+            .ok_or_else(|| Error::EntryNotFound(path.to_owned()))
+    }
+
+    /// Returns the path of the entry at `path` with all symbolic links
+    /// resolved, and the entry, following a symbolic link at `path` itself
+    /// only if `follow` is true.
+    fn resolve(&self, path: &Path, follow: bool) -> Result<(PathBuf, &Node)> {
+        let not_found = || Error::EntryNotFound(path.to_owned());
+
+        // components still to resolve, in reverse order
+        let mut pending = components(path);
+        let mut resolved = PathBuf::new();
+        let mut node = &self.nodes[&resolved];
+        let mut links = 0;
+
+        while let Some(name) = pending.pop() {
+            if name == ".." {
+                resolved.pop();
+                node = &self.nodes[&resolved];
+                continue;
+            }
+
+            if !node.metadata.is_dir() {
+                return Err(Error::NotADirectory(resolved));
+            }
+
+            let candidate = resolved.join(&name);
+            let next = self.nodes.get(&candidate).ok_or_else(not_found)?;
+
+            match &next.link_name {
+                Some(target)
+                    if next.metadata.is_symlink()
+                        && (follow || !pending.is_empty()) =>
+                {
+                    links += 1;
+
+                    if links > MAX_SYMLINKS {
+                        return Err(Error::SymlinkLoop(path.to_owned()));
+                    }
+
+                    if target.has_root() {
+                        resolved = PathBuf::new();
+                        node = &self.nodes[&resolved];
+                    }
+
+                    pending.extend(components(target));
+                }
+
+                _ => {
+                    resolved = candidate;
+                    node = next;
+                }
+            }
+        }
+
+        Ok((resolved, node))
+    }
+}
+
+/// Returns the normal and `..` components of `path`, in reverse order.
+fn components(path: &Path) -> Vec<OsString> {
+    path.components()
+        .rev()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_owned()),
+            Component::ParentDir => Some("..".into()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn sparse_files_are_regular_files() {
+        let regions: &[(u64, &[u8])] = &[(0, &[b's'; 512]), (4096, b"")];
+
+        let tarball = testing::tarball(|builder| {
+            testing::sparse(builder, "sparse", 4096, regions);
+        });
+
+        let (_file, archive) = testing::open(&tarball, ".tar");
+        let mut fs = archive.into_fs().unwrap();
+
+        let metadata = &fs.read_dir("").unwrap()[0].metadata;
+        assert_eq!(metadata.entry_type, EntryType::Regular);
+        assert_eq!(metadata.len, 4096);
+
+        let mut contents = vec![];
+        fs.open("sparse")
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, testing::expand(4096, regions));
+    }
+}
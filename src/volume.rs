@@ -0,0 +1,212 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Writer that splits an archive into numbered volumes of a fixed size,
+/// like `split -d -a 3 -b`.
+///
+/// Writing to `backup.tar.zst` creates `backup.tar.zst.000`,
+/// `backup.tar.zst.001` and so on, which [`Archive::open`] reads as one
+/// archive when opening the first volume.
+///
+/// Volumes are cut at byte offsets, like `split`, not at entry boundaries,
+/// so a single volume is not an archive of its own. Splitting into
+/// independent archives of whole entries is out of scope, because the
+/// compressed size of an entry is only known after writing it.
+///
+/// [`Archive::open`]: crate::Archive::open
+///
+/// # Example
+///
+/// ```no_run
+/// use archive_rs::support::Format;
+/// use archive_rs::{Builder, Volumes};
+///
+/// let volumes = Volumes::create("backup.tar.zst", 4 << 30)?;
+/// let mut builder = Builder::new(volumes, Format::TarZstd)?;
+///
+/// builder.append_dir_all("home", "/home")?;
+///
+/// for path in builder.finish()?.finish()? {
+///     println!("{}", path.display());
+/// }
+/// # Ok::<(), archive_rs::Error>(())
+/// ```
+pub struct Volumes {
+    path: PathBuf,
+    size: u64,
+    paths: Vec<PathBuf>,
+    current: Option<BufWriter<File>>,
+    written: u64,
+}
+
+impl Volumes {
+    /// Returns a writer of volumes of `size` bytes next to `path`, with the
+    /// volume number appended to `path`. The last volume may be smaller.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `size` is zero.
+    pub fn create(path: impl AsRef<Path>, size: u64) -> io::Result<Self> {
+        if size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "volume size must not be zero",
+            ));
+        }
+
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            size,
+            paths: vec![],
+            current: None,
+            written: 0,
+        })
+    }
+
+    /// Returns the paths of the volumes created so far.
+    #[must_use]
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Flushes the last volume and returns the paths of all volumes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if flushing fails.
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.flush()?;
+        Ok(self.paths)
+    }
+
+    /// Returns the volume being written and the number of bytes that still
+    /// fit into it, creating the next volume if the current one is full.
+    fn volume(&mut self) -> io::Result<(&mut BufWriter<File>, u64)> {
+        let volume = match self.current.take() {
+            Some(volume) if self.written < self.size => volume,
+
+            full => {
+                if let Some(mut volume) = full {
+                    volume.flush()?;
+                }
+
+                let path = volume_path(&self.path, self.paths.len());
+                let file = File::create(&path)?;
+
+                self.paths.push(path);
+                self.written = 0;
+
+                BufWriter::new(file)
+            }
+        };
+
+        Ok((self.current.insert(volume), self.size - self.written))
+    }
+}
+
+impl Write for Volumes {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let (volume, remaining) = self.volume()?;
+        let len = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+
+        let n = volume.write(&buf[..len])?;
+        self.written += n as u64;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.as_mut().map_or(Ok(()), Write::flush)
+    }
+}
+
+/// Returns true if `extension` numbers a volume, e.g. `000`.
+pub fn is_volume_number(extension: &str) -> bool {
+    extension.len() == 3 && extension.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Returns the path of volume `n` of the set at `path`.
+fn volume_path(path: &Path, n: usize) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(format!(".{n:03}"));
+    PathBuf::from(path)
+}
+
+/// Returns the paths of all volumes of the set whose first volume is at
+/// `path`, or only `path` if it is not the first volume of a set.
+pub fn volumes(path: &Path) -> Vec<PathBuf> {
+    let first = path.extension().is_some_and(|extension| extension == "000");
+
+    let Some(base) = path.file_stem().filter(|_| first) else {
+        return vec![path.to_owned()];
+    };
+
+    let base = path.with_file_name(base);
+    let mut paths = vec![path.to_owned()];
+
+    loop {
+        let next = volume_path(&base, paths.len());
+
+        if !next.is_file() {
+            return paths;
+        }
+
+        paths.push(next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tar::EntryType;
+
+    use super::*;
+    use crate::testing::{self, Listed};
+    use crate::{Archive, Error};
+
+    #[test]
+    fn volumes_are_read_as_one_archive() {
+        let tarball = testing::tarball(|builder| {
+            testing::file(builder, "a", &[b'a'; 1500]);
+            testing::file(builder, "b", b"b");
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut volumes =
+            Volumes::create(dir.path().join("x.tar"), 1024).unwrap();
+        volumes.write_all(&tarball).unwrap();
+
+        let paths = volumes.finish().unwrap();
+        assert_eq!(paths.len(), tarball.len().div_ceil(1024));
+
+        let mut archive = Archive::open(&paths[0]).unwrap();
+        let mut read = vec![];
+
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut data = vec![];
+            io::Read::read_to_end(&mut entry, &mut data).unwrap();
+            read.push(Listed::new(&path, EntryType::Regular, &data, None));
+        }
+
+        assert_eq!(read, testing::list(&tarball));
+    }
+
+    #[test]
+    fn volumes_cannot_be_appended_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("x.tar.000");
+        fs::write(&path, testing::tarball(|_| {})).unwrap();
+
+        let appended = Archive::open_append(&path);
+        assert!(matches!(appended, Err(Error::UnsupportedAppendVolume(_))));
+    }
+}